@@ -0,0 +1,241 @@
+use std::fmt::Display;
+
+use anyhow::Result;
+
+use crate::decimal::Value;
+
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+    Between(usize, usize),
+}
+
+impl Arity {
+    fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(n) => count == *n,
+            Arity::AtLeast(n) => count >= *n,
+            Arity::Between(min, max) => (*min..=*max).contains(&count),
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+            Arity::Between(min, max) => write!(f, "{} to {}", min, max),
+        }
+    }
+}
+
+// Most built-ins give a number, text built-ins such as `to_words` give a `Builtin<String>`
+pub struct Builtin<T = f64> {
+    pub name: &'static str,
+    pub arity: Arity,
+    apply: fn(&[f64]) -> Result<T>,
+}
+
+impl<T> Builtin<T> {
+    pub fn call(&self, args: &[f64]) -> Result<T> {
+        if !self.arity.accepts(args.len()) {
+            anyhow::bail!("Function {} expects {} argument(s) but got {}", self.name, self.arity, args.len())
+        }
+
+        (self.apply)(args)
+    }
+}
+
+static BUILTINS: [Builtin; 28] = [
+    Builtin { name: "abs", arity: Arity::Exact(1), apply: |args| Ok(args[0].abs()) },
+    Builtin { name: "abs_diff", arity: Arity::Exact(2), apply: |args| Ok((args[0] - args[1]).abs()) },
+    Builtin { name: "max_abs", arity: Arity::Exact(2), apply: |args| Ok(args[0].abs().max(args[1].abs())) },
+    Builtin { name: "floor", arity: Arity::Exact(1), apply: |args| Ok(args[0].floor()) },
+    Builtin { name: "ceil", arity: Arity::Exact(1), apply: |args| Ok(args[0].ceil()) },
+    // Halfway cases round away from zero, round(-2.5) is -3
+    Builtin { name: "round", arity: Arity::Exact(1), apply: |args| Ok(args[0].round()) },
+    Builtin { name: "sqrt", arity: Arity::Exact(1), apply: |args| Ok(args[0].sqrt()) },
+    Builtin { name: "sin", arity: Arity::Exact(1), apply: |args| Ok(args[0].sin()) },
+    Builtin { name: "cos", arity: Arity::Exact(1), apply: |args| Ok(args[0].cos()) },
+    Builtin { name: "tan", arity: Arity::Exact(1), apply: |args| Ok(args[0].tan()) },
+    Builtin { name: "copysign", arity: Arity::Exact(2), apply: |args| Ok(args[0].copysign(args[1])) },
+    Builtin { name: "nextafter", arity: Arity::Exact(2), apply: nextafter },
+    Builtin { name: "sinc", arity: Arity::Exact(1), apply: sinc },
+    Builtin { name: "logistic", arity: Arity::Exact(1), apply: |args| Ok(1.0 / (1.0 + (-args[0]).exp())) },
+    // NaN stays NaN, where f64::max would turn it into 0
+    Builtin { name: "relu", arity: Arity::Exact(1), apply: |args| Ok(if args[0] < 0.0 { 0.0 } else { args[0] }) },
+    Builtin { name: "map_range", arity: Arity::Between(5, 6), apply: map_range },
+    Builtin { name: "min", arity: Arity::AtLeast(1), apply: min },
+    Builtin { name: "max", arity: Arity::AtLeast(1), apply: max },
+    Builtin { name: "nanmin", arity: Arity::AtLeast(1), apply: nanmin },
+    Builtin { name: "nanmax", arity: Arity::AtLeast(1), apply: nanmax },
+    Builtin { name: "sum", arity: Arity::AtLeast(1), apply: |args| Ok(sum(args.iter().copied())) },
+    Builtin { name: "mean", arity: Arity::AtLeast(1), apply: mean },
+    Builtin { name: "nansum", arity: Arity::AtLeast(1), apply: |args| Ok(sum(args.iter().copied().filter(|n| !n.is_nan()))) },
+    Builtin { name: "nanmean", arity: Arity::AtLeast(1), apply: nanmean },
+    Builtin { name: "percent_change", arity: Arity::Exact(2), apply: percent_change },
+    Builtin { name: "percent_of", arity: Arity::Exact(2), apply: percent_of },
+    Builtin { name: "wrap", arity: Arity::Exact(2), apply: wrap },
+    Builtin { name: "wrap_signed", arity: Arity::Exact(2), apply: wrap_signed },
+];
+
+// Text can't take part in arithmetic, so a call to one of these has to make up a whole statement
+static TEXT_BUILTINS: [Builtin<String>; 2] = [
+    Builtin { name: "to_words", arity: Arity::Exact(1), apply: |args| crate::format::to_words(args[0]) },
+    Builtin { name: "to_roman", arity: Arity::Exact(1), apply: |args| crate::format::to_roman(args[0]) },
+];
+
+type TextReader = fn(&str) -> Result<f64>;
+
+// Built-ins that read a single quoted argument, `from_roman("MMXXIV")` is 2024
+static TEXT_READERS: [(&str, TextReader); 1] = [
+    ("from_roman", crate::format::from_roman),
+];
+
+static CONSTANTS: [(&str, f64); 3] = [
+    ("nan", f64::NAN),
+    ("pi", std::f64::consts::PI),
+    ("π", std::f64::consts::PI),
+];
+
+pub fn function(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+pub fn functions() -> impl Iterator<Item = &'static Builtin> {
+    BUILTINS.iter()
+}
+
+pub fn text_function(name: &str) -> Option<&'static Builtin<String>> {
+    TEXT_BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+pub fn text_functions() -> impl Iterator<Item = &'static Builtin<String>> {
+    TEXT_BUILTINS.iter()
+}
+
+pub fn text_reader(name: &str) -> Option<TextReader> {
+    TEXT_READERS.iter().find(|(label, _)| *label == name).map(|(_, read)| *read)
+}
+
+pub fn text_readers<'a>() -> impl Iterator<Item = &'a str> {
+    TEXT_READERS.iter().map(|(label, _)| *label)
+}
+
+pub fn constants<'a>() -> impl Iterator<Item = &'a str> {
+    CONSTANTS.iter().map(|(label, _)| *label)
+}
+
+pub fn constant(name: &str) -> Option<f64> {
+    CONSTANTS.iter().find(|(label, _)| *label == name).map(|(_, value)| *value)
+}
+
+// Any NaN argument makes the result NaN
+fn min(args: &[f64]) -> Result<f64> {
+    Ok(args.iter().copied().reduce(|a, b| if a.is_nan() || b.is_nan() { f64::NAN } else { a.min(b) }).unwrap_or(f64::NAN))
+}
+
+fn max(args: &[f64]) -> Result<f64> {
+    Ok(args.iter().copied().reduce(|a, b| if a.is_nan() || b.is_nan() { f64::NAN } else { a.max(b) }).unwrap_or(f64::NAN))
+}
+
+// NaN arguments are skipped. If every argument is NaN there is nothing to compare, so the result is NaN.
+fn nanmin(args: &[f64]) -> Result<f64> {
+    Ok(args.iter().copied().filter(|n| !n.is_nan()).reduce(f64::min).unwrap_or(f64::NAN))
+}
+
+fn nanmax(args: &[f64]) -> Result<f64> {
+    Ok(args.iter().copied().filter(|n| !n.is_nan()).reduce(f64::max).unwrap_or(f64::NAN))
+}
+
+// Aggregates follow one convention: the plain name propagates NaN and the `nan` prefixed one skips NaN arguments
+// Goes through the same addition as `+`, so with the `decimal` feature `sum(0.1, 0.2)` is exactly 0.3
+fn sum(numbers: impl Iterator<Item = f64>) -> f64 {
+    total(numbers).to_f64()
+}
+
+fn total(numbers: impl Iterator<Item = f64>) -> Value {
+    numbers.map(Value::from).fold(Value::from(0.0), |total, n| total + n)
+}
+
+fn mean(args: &[f64]) -> Result<f64> {
+    Ok((total(args.iter().copied()) / Value::from(args.len() as f64)).to_f64())
+}
+
+fn nanmean(args: &[f64]) -> Result<f64> {
+    let numbers: Vec<f64> = args.iter().copied().filter(|n| !n.is_nan()).collect();
+    if numbers.is_empty() {
+        return Ok(f64::NAN);
+    }
+    mean(&numbers)
+}
+
+// The unnormalized sin(x) / x, with its limit of 1 at zero
+fn sinc(args: &[f64]) -> Result<f64> {
+    let x = args[0];
+    Ok(if x == 0.0 { 1.0 } else { x.sin() / x })
+}
+
+// The next representable value after x in the direction of y, or y itself when they are equal
+fn nextafter(args: &[f64]) -> Result<f64> {
+    let (x, y) = (args[0], args[1]);
+    Ok(if x.is_nan() || y.is_nan() {
+        f64::NAN
+    } else if x < y {
+        x.next_up()
+    } else if x > y {
+        x.next_down()
+    } else {
+        y
+    })
+}
+
+// map_range(x, in_lo, in_hi, out_lo, out_hi) linearly remaps x. A non-zero sixth argument clamps the
+// result to the output interval.
+fn map_range(args: &[f64]) -> Result<f64> {
+    let (x, in_lo, in_hi, out_lo, out_hi) = (args[0], args[1], args[2], args[3], args[4]);
+    if in_hi == in_lo {
+        anyhow::bail!("Input interval of map_range must not be empty")
+    }
+
+    let mapped = out_lo + (x - in_lo) * (out_hi - out_lo) / (in_hi - in_lo);
+
+    match args.get(5) {
+        Some(clamp) if *clamp != 0.0 => Ok(mapped.clamp(out_lo.min(out_hi), out_lo.max(out_hi))),
+        _ => Ok(mapped)
+    }
+}
+
+// Relative to the magnitude of the old value, so going from -100 to -50 is a 50% increase
+fn percent_change(args: &[f64]) -> Result<f64> {
+    let (old, new) = (args[0], args[1]);
+    if old == 0.0 {
+        anyhow::bail!("Cannot compute a percent change from zero")
+    }
+    Ok((new - old) / old.abs() * 100.0)
+}
+
+fn percent_of(args: &[f64]) -> Result<f64> {
+    let (part, whole) = (args[0], args[1]);
+    if whole == 0.0 {
+        anyhow::bail!("Cannot compute a percentage of zero")
+    }
+    Ok(part / whole * 100.0)
+}
+
+// Wraps a periodic value into [0, period), e.g. wrap(-10, 360) is 350
+fn wrap(args: &[f64]) -> Result<f64> {
+    let (value, period) = (args[0], args[1]);
+    if period == 0.0 {
+        anyhow::bail!("Period of wrap must not be zero")
+    }
+    Ok(value.rem_euclid(period))
+}
+
+// Wraps a periodic value into (-period / 2, period / 2], e.g. wrap_signed(270, 360) is -90
+fn wrap_signed(args: &[f64]) -> Result<f64> {
+    let wrapped = wrap(args)?;
+    let period = args[1].abs();
+    Ok(if wrapped > period / 2.0 { wrapped - period } else { wrapped })
+}
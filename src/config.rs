@@ -0,0 +1,78 @@
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Notation {
+    #[default]
+    Plain,
+    // One digit before the decimal point, e.g. `1.2345e4`
+    Scientific,
+    // Exponents are multiples of three, e.g. `12.345e3`
+    Engineering,
+}
+
+// Separators used when reading and displaying numbers
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Locale {
+    // 1,234.56
+    #[default]
+    English,
+    // 1.234,56
+    German,
+    // 1 234,56, grouped with a narrow no-break space
+    French,
+}
+
+impl Locale {
+    pub fn decimal_separator(self) -> char {
+        match self {
+            Locale::English => '.',
+            Locale::German | Locale::French => ',',
+        }
+    }
+
+    pub fn group_separator(self) -> char {
+        match self {
+            Locale::English => ',',
+            Locale::German => '.',
+            Locale::French => '\u{202f}',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    // Upper bound on the number of results a single call to evaluate produces. None means unbounded.
+    pub max_results: Option<usize>,
+    // Reject decimal literals with redundant leading zeros such as `007`. `0` and `0.5` are still accepted.
+    // Only the decimal digits of a literal are checked, so any prefixed notation is unaffected.
+    pub strict_leading_zeros: bool,
+    // Stop number literals at `e`/`E`, so `2e3` reads as `2` followed by the name `e3`
+    pub disable_scientific_notation: bool,
+    // Treat the locale's group separator between digits of a number's integer part as a thousands separator, so
+    // `1,234.56` is 1234.56. This takes precedence over commas separating function arguments: `max(1,2)` reads as
+    // `max(12)`, write `max(1, 2)` instead.
+    pub grouped_number_input: bool,
+    // Decimal and group separators for input and output. A decimal comma directly followed by a digit belongs to
+    // the number, so in German `max(1,5)` reads as `max(1.5)`.
+    pub locale: Locale,
+    // Report an error instead of storing NaN or an infinity in a variable
+    pub reject_non_finite: bool,
+    // Report an error for operands that follow each other without an operator, such as `5 3`, instead of evaluating
+    // them as separate statements
+    pub require_operators: bool,
+    // Saturate displayed results to the inclusive [min, max] window. Reversed bounds are swapped and a NaN bound is
+    // ignored. Stored values are left untouched.
+    pub display_clamp: Option<(f64, f64)>,
+    pub notation: Notation,
+    // Digits after the decimal point in plain notation, e.g. Some(2) shows 1/3 as 0.33
+    pub decimals: Option<usize>,
+    // Round displayed results to this many significant digits, this takes precedence over decimals
+    pub significant_figures: Option<usize>,
+    // Separate thousands with the locale's group separator in plain notation, e.g. 1,234,567.5
+    pub thousands_separator: bool,
+    // Show integer results in base 2, 8 or 16 with a 0b, 0o or 0x prefix. Other bases are ignored.
+    pub base: Option<u32>,
+    // Round values to this many decimal places before storing them in a variable. Unlike the display options this
+    // changes the stored value, so later computations use the rounded number.
+    pub storage_precision: Option<u32>,
+    // Only report errors in the REPL, toggled with `silent on` and `silent off`
+    pub silent: bool,
+}
@@ -0,0 +1,122 @@
+// The value every expression evaluates to. With the `decimal` feature, `+ - * /` are exact: operands are taken at
+// their shortest decimal representation, and the exact result is carried to the next operation rather than rounded
+// to an f64 in between, so `0.1 + 0.2` is the same value as `0.3` and `1/3*3` is 1. Anything a decimal cannot hold
+// (NaN, infinities, very large or very small magnitudes) falls back to plain f64 arithmetic, as do all
+// transcendental functions, which only ever see the f64.
+
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Value {
+    float: f64,
+    // The exact result of the operations that gave this value, None when it is only known as `float`
+    #[cfg(feature = "decimal")]
+    exact: Option<Decimal>,
+}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Value {
+    pub fn to_f64(self) -> f64 {
+        self.float
+    }
+
+    // Applies the operation exactly when possible, otherwise to the f64s
+    fn apply(self, other: Value, operation: Operation) -> Value {
+        self.exact(other, operation).unwrap_or_else(|| Value::from(match operation {
+            Operation::Add => self.float + other.float,
+            Operation::Sub => self.float - other.float,
+            Operation::Mul => self.float * other.float,
+            Operation::Div => self.float / other.float,
+        }))
+    }
+
+    #[cfg(feature = "decimal")]
+    fn exact(self, other: Value, operation: Operation) -> Option<Value> {
+        let (a, b) = (self.to_decimal()?, other.to_decimal()?);
+        let exact = match operation {
+            Operation::Add => a.checked_add(b),
+            Operation::Sub => a.checked_sub(b),
+            Operation::Mul => a.checked_mul(b),
+            Operation::Div => a.checked_div(b),
+        }?.normalize();
+
+        Some(Value { float: exact.to_string().parse().ok()?, exact: Some(exact) })
+    }
+
+    #[cfg(not(feature = "decimal"))]
+    fn exact(self, _: Value, _: Operation) -> Option<Value> {
+        None
+    }
+
+    #[cfg(feature = "decimal")]
+    fn to_decimal(self) -> Option<Decimal> {
+        if self.exact.is_some() || !self.float.is_finite() {
+            return self.exact;
+        }
+        Decimal::from_str_exact(&self.float.to_string()).ok()
+    }
+}
+
+impl From<f64> for Value {
+    fn from(float: f64) -> Value {
+        Value {
+            float,
+            #[cfg(feature = "decimal")]
+            exact: None,
+        }
+    }
+}
+
+impl Add for Value {
+    type Output = Value;
+
+    fn add(self, other: Value) -> Value {
+        self.apply(other, Operation::Add)
+    }
+}
+
+impl Sub for Value {
+    type Output = Value;
+
+    fn sub(self, other: Value) -> Value {
+        self.apply(other, Operation::Sub)
+    }
+}
+
+impl Mul for Value {
+    type Output = Value;
+
+    fn mul(self, other: Value) -> Value {
+        self.apply(other, Operation::Mul)
+    }
+}
+
+impl Div for Value {
+    type Output = Value;
+
+    fn div(self, other: Value) -> Value {
+        self.apply(other, Operation::Div)
+    }
+}
+
+impl Neg for Value {
+    type Output = Value;
+
+    fn neg(self) -> Value {
+        Value {
+            float: -self.float,
+            #[cfg(feature = "decimal")]
+            exact: self.exact.map(|exact| -exact),
+        }
+    }
+}
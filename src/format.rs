@@ -0,0 +1,422 @@
+use anyhow::Result;
+
+use crate::{Config, Notation};
+
+const ONES: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+    "ten", "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const TENS: [&str; 10] = ["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+const SCALES: [&str; 5] = ["", "thousand", "million", "billion", "trillion"];
+const NUMERALS: [(u32, &str); 13] = [
+    (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+    (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+];
+
+// Unlike f64::clamp this never panics: reversed bounds are swapped and a NaN bound leaves that side open.
+// NaN values stay NaN.
+fn clamp_to_window(value: f64, (min, max): (f64, f64)) -> f64 {
+    let (low, high) = if max < min { (max, min) } else { (min, max) };
+    if value.is_nan() { value } else { value.max(low).min(high) }
+}
+
+// Every displayed result goes through here. The options apply in a fixed order, so each combination has one meaning:
+// 1. `display_clamp` saturates the value. NaN and infinities are then spelled out and nothing else applies.
+// 2. A `base` of 2, 8 or 16 shows integers with a `0b`, `0o` or `0x` prefix and ignores every option below.
+//    Results with a fractional part fall through to base 10.
+// 3. `significant_figures` rounds the value and takes precedence over `decimals`.
+// 4. Scientific and engineering notation show the rounded value, `decimals` and `thousands_separator` are ignored.
+// 5. In plain notation `decimals` fixes the number of decimal places, then `thousands_separator` groups the integer part.
+pub fn format_result(value: f64, config: &Config) -> String {
+    let value = match config.display_clamp {
+        Some(window) => clamp_to_window(value, window),
+        None => value,
+    };
+
+    // Spelled out instead of Rust's `NaN` and `inf`
+    if value.is_nan() {
+        return "not a number".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "infinity" } else { "-infinity" }.to_string();
+    }
+
+    if let Some(formatted) = config.base.and_then(|base| in_base(value, base)) {
+        return formatted;
+    }
+
+    let value = match config.significant_figures {
+        Some(figures) => round_to_significant_figures(value, figures),
+        None => value,
+    };
+
+    let formatted = match (config.notation, config.decimals) {
+        (Notation::Scientific, _) => format!("{:e}", value),
+        (Notation::Engineering, _) => engineering(value),
+        (Notation::Plain, Some(decimals)) if config.significant_figures.is_none() => format!("{:.*}", decimals, value),
+        (Notation::Plain, _) => format!("{}", value),
+    };
+
+    let grouping = match config.notation {
+        Notation::Plain if config.thousands_separator => Some(config.locale.group_separator()),
+        _ => None,
+    };
+    localize(&formatted, grouping, config.locale.decimal_separator())
+}
+
+fn in_base(value: f64, base: u32) -> Option<String> {
+    // i64::MAX as f64 rounds up to 2^63, which is already out of range
+    if value.fract() != 0.0 || value.abs() >= i64::MAX as f64 {
+        return None;
+    }
+
+    let magnitude = (value as i64).unsigned_abs();
+    let sign = if value < 0.0 { "-" } else { "" };
+    match base {
+        2 => Some(format!("{}0b{:b}", sign, magnitude)),
+        8 => Some(format!("{}0o{:o}", sign, magnitude)),
+        16 => Some(format!("{}0x{:x}", sign, magnitude)),
+        _ => None,
+    }
+}
+
+// Goes through the decimal representation so 1234.5678 with 3 figures is exactly 1230
+fn round_to_significant_figures(value: f64, figures: usize) -> f64 {
+    format!("{:.*e}", figures.max(1) - 1, value).parse().expect("Should be a valid number")
+}
+
+// Swaps in the locale's decimal separator and optionally groups the integer part, e.g. 1.234,5 in German
+fn localize(formatted: &str, grouping: Option<char>, decimal_separator: char) -> String {
+    let (sign, digits) = formatted.split_at(if formatted.starts_with('-') { 1 } else { 0 });
+    let (integer, fraction) = match digits.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (digits, None),
+    };
+
+    let mut grouped = String::new();
+    for (i, c) in integer.chars().enumerate() {
+        if let Some(separator) = grouping.filter(|_| i > 0 && (integer.len() - i) % 3 == 0) {
+            grouped.push(separator);
+        }
+        grouped.push(c);
+    }
+
+    match fraction {
+        Some(fraction) => format!("{}{}{}{}", sign, grouped, decimal_separator, fraction),
+        None => format!("{}{}", sign, grouped),
+    }
+}
+
+// Works on the shortest scientific representation so the mantissa digits are never disturbed by
+// floating point scaling
+fn engineering(value: f64) -> String {
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific.split_once('e').expect("Should be in scientific notation");
+    let exponent: i32 = exponent.parse().expect("Should be a valid exponent");
+    let shift = exponent.rem_euclid(3) as usize;
+
+    let mut digits = mantissa.replace('.', "");
+    while digits.len() < shift + 1 {
+        digits.push('0');
+    }
+    let (integer, fraction) = digits.split_at(shift + 1);
+
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    let point = if fraction.is_empty() { "" } else { "." };
+    format!("{}{}{}{}e{}", sign, integer, point, fraction, exponent - shift as i32)
+}
+
+// Lays out name/value rows in two columns, names aligned to the left and values to the right
+pub fn format_table(rows: &[(String, String)]) -> String {
+    let name_width = rows.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    let value_width = rows.iter().map(|(_, value)| value.chars().count()).max().unwrap_or(0);
+
+    rows.iter()
+        .map(|(name, value)| format!("{:<name_width$}  {:>value_width$}", name, value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Spells out a number in American English: tens and ones are hyphenated ("twenty-three") and no "and" is
+// inserted after hundreds ("one hundred five"). A fractional part is read digit by digit after "point".
+pub fn to_words(n: f64) -> Result<String> {
+    if !n.is_finite() || n.abs() >= 1e15 {
+        anyhow::bail!("Cannot spell out {}: only finite numbers below one quadrillion are supported", n)
+    }
+
+    let text = format!("{}", n.abs());
+    let (integer, fraction) = text.split_once('.').unwrap_or((&text, ""));
+    let integer: u64 = integer.parse()?;
+
+    let mut words = if integer == 0 { ONES[0].to_string() } else { integer_to_words(integer) };
+
+    if !fraction.is_empty() {
+        words.push_str(" point");
+        for digit in fraction.chars() {
+            let digit = digit.to_digit(10).expect("Should be a decimal digit") as usize;
+            words.push(' ');
+            words.push_str(ONES[digit]);
+        }
+    }
+
+    if n < 0.0 {
+        words.insert_str(0, "minus ");
+    }
+
+    Ok(words)
+}
+
+fn integer_to_words(mut n: u64) -> String {
+    let mut groups = vec![];
+
+    for scale in SCALES {
+        let chunk = n % 1000;
+        if chunk > 0 {
+            let chunk = chunk_to_words(chunk);
+            groups.push(if scale.is_empty() { chunk } else { format!("{} {}", chunk, scale) });
+        }
+        n /= 1000;
+    }
+
+    groups.reverse();
+    groups.join(" ")
+}
+
+// Spells out a number between 1 and 999
+fn chunk_to_words(n: u64) -> String {
+    let hundreds = (n / 100) as usize;
+    let rest = (n % 100) as usize;
+    let mut words = vec![];
+
+    if hundreds > 0 {
+        words.push(format!("{} hundred", ONES[hundreds]));
+    }
+
+    if rest >= 20 {
+        let (tens, ones) = (TENS[rest / 10], rest % 10);
+        words.push(if ones == 0 { tens.to_string() } else { format!("{}-{}", tens, ONES[ones]) });
+    } else if rest > 0 {
+        words.push(ONES[rest].to_string());
+    }
+
+    words.join(" ")
+}
+
+pub fn to_roman(n: f64) -> Result<String> {
+    if n.fract() != 0.0 || !(1.0..=3999.0).contains(&n) {
+        anyhow::bail!("Cannot write {} in Roman numerals: only whole numbers from 1 to 3999 are supported", n)
+    }
+
+    let mut n = n as u32;
+    let mut numeral = String::new();
+
+    for (value, symbol) in NUMERALS {
+        while n >= value {
+            numeral.push_str(symbol);
+            n -= value;
+        }
+    }
+
+    Ok(numeral)
+}
+
+// Only canonical numerals are accepted, so `IIII` or `IC` are rejected rather than guessed at. The result is an f64
+// like every other value, matching the argument of `to_roman`.
+pub fn from_roman(numeral: &str) -> Result<f64> {
+    let mut rest = numeral.trim();
+    let mut n = 0;
+
+    for (value, symbol) in NUMERALS {
+        while let Some(stripped) = rest.strip_prefix(symbol) {
+            n += value;
+            rest = stripped;
+        }
+    }
+
+    if !rest.is_empty() || to_roman(n as f64).ok().as_deref() != Some(numeral.trim()) {
+        anyhow::bail!("Invalid Roman numeral: {}", numeral)
+    }
+
+    Ok(n as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Locale;
+
+    #[test]
+    fn test_format_result_without_clamp() {
+        assert_eq!(format_result(150.0, &Config::default()), "150");
+    }
+
+    #[test]
+    fn test_format_result_clamped() {
+        let config = Config { display_clamp: Some((0.0, 100.0)), ..Config::default() };
+        assert_eq!(format_result(150.0, &config), "100");
+        assert_eq!(format_result(-5.0, &config), "0");
+        assert_eq!(format_result(42.5, &config), "42.5");
+    }
+
+    #[test]
+    fn test_format_result_clamped_with_unusual_window() {
+        let config = Config { display_clamp: Some((100.0, 0.0)), ..Config::default() };
+        assert_eq!(format_result(150.0, &config), "100");
+        assert_eq!(format_result(-5.0, &config), "0");
+
+        let config = Config { display_clamp: Some((f64::NAN, 100.0)), ..Config::default() };
+        assert_eq!(format_result(-5.0, &config), "-5");
+        assert_eq!(format_result(150.0, &config), "100");
+        assert_eq!(format_result(f64::NAN, &config), "not a number");
+    }
+
+    #[test]
+    fn test_format_result_engineering() {
+        let config = Config { notation: Notation::Engineering, ..Config::default() };
+        assert_eq!(format_result(12345.0, &config), "12.345e3");
+        assert_eq!(format_result(0.0005, &config), "500e-6");
+        assert_eq!(format_result(-12345.0, &config), "-12.345e3");
+        assert_eq!(format_result(1.5, &config), "1.5e0");
+        assert_eq!(format_result(0.0, &config), "0e0");
+    }
+
+    #[test]
+    fn test_format_result_scientific() {
+        let config = Config { notation: Notation::Scientific, ..Config::default() };
+        assert_eq!(format_result(12345.0, &config), "1.2345e4");
+        assert_eq!(format_result(f64::INFINITY, &config), "infinity");
+    }
+
+    #[test]
+    fn test_format_result_special_values_as_words() {
+        assert_eq!(format_result(f64::NAN, &Config::default()), "not a number");
+        assert_eq!(format_result(f64::INFINITY, &Config::default()), "infinity");
+        assert_eq!(format_result(f64::NEG_INFINITY, &Config::default()), "-infinity");
+    }
+
+    #[test]
+    fn test_format_result_grouping_with_decimals() {
+        let config = Config { thousands_separator: true, decimals: Some(2), ..Config::default() };
+        assert_eq!(format_result(1234567.891, &config), "1,234,567.89");
+        assert_eq!(format_result(-1234.5, &config), "-1,234.50");
+        assert_eq!(format_result(999.0, &config), "999.00");
+    }
+
+    #[test]
+    fn test_format_result_significant_figures_over_decimals() {
+        let config = Config { significant_figures: Some(3), decimals: Some(5), thousands_separator: true, ..Config::default() };
+        assert_eq!(format_result(1234.5678, &config), "1,230");
+        assert_eq!(format_result(0.000123456, &config), "0.000123");
+    }
+
+    #[test]
+    fn test_format_result_base_overrides_decimals() {
+        let config = Config { base: Some(16), decimals: Some(2), thousands_separator: true, ..Config::default() };
+        assert_eq!(format_result(255.0, &config), "0xff");
+        assert_eq!(format_result(-10.0, &config), "-0xa");
+        assert_eq!(format_result(2.5, &config), "2.50");
+        assert_eq!(format_result(5.0, &Config { base: Some(2), ..Config::default() }), "0b101");
+    }
+
+    #[test]
+    fn test_format_result_base_at_i64_boundary() {
+        let config = Config { base: Some(16), ..Config::default() };
+        assert_eq!(format_result(2f64.powi(62), &config), "0x4000000000000000");
+        assert_eq!(format_result(2f64.powi(63), &config), format_result(2f64.powi(63), &Config::default()));
+    }
+
+    #[test]
+    fn test_format_result_notation_ignores_decimals_and_grouping() {
+        let config = Config { notation: Notation::Scientific, significant_figures: Some(2), decimals: Some(4), thousands_separator: true, ..Config::default() };
+        assert_eq!(format_result(12345.0, &config), "1.2e4");
+    }
+
+    #[test]
+    fn test_format_result_german_locale() {
+        let config = Config { locale: Locale::German, thousands_separator: true, ..Config::default() };
+        assert_eq!(format_result(1234.56, &config), "1.234,56");
+        assert_eq!(format_result(-1234567.0, &config), "-1.234.567");
+        let config = Config { locale: Locale::German, notation: Notation::Scientific, ..Config::default() };
+        assert_eq!(format_result(12345.0, &config), "1,2345e4");
+    }
+
+    #[test]
+    fn test_format_result_french_locale() {
+        let config = Config { locale: Locale::French, thousands_separator: true, decimals: Some(1), ..Config::default() };
+        assert_eq!(format_result(1234.56, &config), "1\u{202f}234,6");
+    }
+
+    #[test]
+    fn test_format_table_aligns_columns() {
+        let rows = vec![
+            ("x".to_string(), "5".to_string()),
+            ("total".to_string(), "-12.5".to_string()),
+            ("π".to_string(), "100".to_string()),
+        ];
+        assert_eq!(format_table(&rows), "x          5\ntotal  -12.5\nπ        100");
+    }
+
+    #[test]
+    fn test_format_table_empty() {
+        assert_eq!(format_table(&[]), "");
+    }
+
+    #[test]
+    fn test_to_roman() {
+        assert_eq!(to_roman(2024.0).unwrap(), "MMXXIV");
+        assert_eq!(to_roman(3999.0).unwrap(), "MMMCMXCIX");
+        assert_eq!(to_roman(4.0).unwrap(), "IV");
+    }
+
+    #[test]
+    fn test_to_roman_out_of_range() {
+        assert!(to_roman(0.0).is_err());
+        assert!(to_roman(4000.0).is_err());
+        assert!(to_roman(2.5).is_err());
+    }
+
+    #[test]
+    fn test_from_roman() {
+        assert_eq!(from_roman("MMXXIV").unwrap(), 2024.0);
+        assert_eq!(from_roman("MCMXCIV").unwrap(), 1994.0);
+    }
+
+    #[test]
+    fn test_from_roman_malformed() {
+        assert!(from_roman("IIII").is_err());
+        assert!(from_roman("IC").is_err());
+        assert!(from_roman("MMMM").is_err());
+        assert!(from_roman("XA").is_err());
+        assert!(from_roman("").is_err());
+    }
+
+    #[test]
+    fn test_to_words_zero() {
+        assert_eq!(to_words(0.0).unwrap(), "zero");
+    }
+
+    #[test]
+    fn test_to_words_hundreds_with_hyphenated_tens() {
+        assert_eq!(to_words(123.0).unwrap(), "one hundred twenty-three");
+    }
+
+    #[test]
+    fn test_to_words_million() {
+        assert_eq!(to_words(1000000.0).unwrap(), "one million");
+    }
+
+    #[test]
+    fn test_to_words_billions() {
+        assert_eq!(to_words(2_000_015_040.0).unwrap(), "two billion fifteen thousand forty");
+    }
+
+    #[test]
+    fn test_to_words_negative_with_fraction() {
+        assert_eq!(to_words(-12.05).unwrap(), "minus twelve point zero five");
+    }
+
+    #[test]
+    fn test_to_words_non_finite() {
+        assert!(to_words(f64::INFINITY).is_err());
+    }
+}
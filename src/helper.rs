@@ -0,0 +1,106 @@
+use std::borrow::Cow;
+
+use rustyline::completion::Completer;
+use rustyline::highlight::{Highlighter, MatchingBracketHighlighter};
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::Helper;
+
+use crate::token::{closing_bracket, is_closing_bracket, Token, TokenStream};
+
+#[derive(Debug, PartialEq)]
+pub enum ParenBalance {
+    Balanced,
+    // The missing closing brackets, innermost first, so `[(1` is missing ")]"
+    Unclosed(String),
+    UnexpectedClose(char),
+}
+
+pub fn paren_balance(input: &str) -> ParenBalance {
+    let mut ts = TokenStream::from_chars(input);
+    let mut expected = vec![];
+
+    loop {
+        match ts.next() {
+            Ok(Some(Token::Symbol(c))) if closing_bracket(c).is_some() => expected.extend(closing_bracket(c)),
+            Ok(Some(Token::Symbol(c))) if is_closing_bracket(c) => {
+                if expected.pop() != Some(c) {
+                    return ParenBalance::UnexpectedClose(c);
+                }
+            }
+            Ok(Some(_)) => {}
+            Ok(None) => break,
+            // Invalid tokens are reported when the input is evaluated, keep counting past them
+            Err(_) => {}
+        }
+    }
+
+    if expected.is_empty() { ParenBalance::Balanced } else { ParenBalance::Unclosed(expected.iter().rev().collect()) }
+}
+
+// Prompt helper that highlights the bracket matching the one under the cursor and refuses to
+// submit input whose brackets are unbalanced
+#[derive(Default)]
+pub struct PromptHelper {
+    highlighter: MatchingBracketHighlighter,
+}
+
+impl Validator for PromptHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        Ok(match paren_balance(ctx.input()) {
+            ParenBalance::Balanced => ValidationResult::Valid(None),
+            ParenBalance::Unclosed(missing) => ValidationResult::Invalid(Some(format!(" -- missing closing '{}'", missing))),
+            ParenBalance::UnexpectedClose(c) => ValidationResult::Invalid(Some(format!(" -- unexpected closing '{}'", c))),
+        })
+    }
+}
+
+impl Highlighter for PromptHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        self.highlighter.highlight(line, pos)
+    }
+
+    fn highlight_char(&self, line: &str, pos: usize) -> bool {
+        self.highlighter.highlight_char(line, pos)
+    }
+}
+
+impl Completer for PromptHelper {
+    type Candidate = String;
+}
+
+impl Hinter for PromptHelper {
+    type Hint = String;
+}
+
+impl Helper for PromptHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paren_balance_unclosed() {
+        assert_eq!(paren_balance("(1+2"), ParenBalance::Unclosed(")".to_string()));
+        assert_eq!(paren_balance("[{1+2}"), ParenBalance::Unclosed("]".to_string()));
+        assert_eq!(paren_balance("{[(1"), ParenBalance::Unclosed(")]}".to_string()));
+    }
+
+    #[test]
+    fn test_paren_balance_balanced() {
+        assert_eq!(paren_balance("(1+2)"), ParenBalance::Balanced);
+        assert_eq!(paren_balance(""), ParenBalance::Balanced);
+    }
+
+    #[test]
+    fn test_paren_balance_unexpected_close() {
+        assert_eq!(paren_balance("1+2)"), ParenBalance::UnexpectedClose(')'));
+        assert_eq!(paren_balance(")("), ParenBalance::UnexpectedClose(')'));
+        assert_eq!(paren_balance("(1+2]"), ParenBalance::UnexpectedClose(']'));
+    }
+
+    #[test]
+    fn test_paren_balance_ignores_invalid_tokens() {
+        assert_eq!(paren_balance("((1 @ 2)"), ParenBalance::Unclosed(")".to_string()));
+    }
+}
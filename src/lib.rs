@@ -1,26 +1,168 @@
+use std::fmt::Display;
 use std::process::exit;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use serde::Serialize;
+use thiserror::Error;
 
-use token::{Token, TokenStream};
+use decimal::Value;
+use token::{closing_bracket, is_closing_bracket, TokenStream, TokenizationError};
 
 use rustyline::error::ReadlineError;
-use rustyline::{DefaultEditor};
+use rustyline::history::DefaultHistory;
+use rustyline::Editor;
 
+use helper::PromptHelper;
+
+pub use config::{Config, Locale, Notation};
+pub use format::{from_roman, to_roman, to_words};
+pub use token::{parse_number, ReparseState, Token};
+
+mod builtins;
+mod config;
+mod decimal;
+mod format;
+mod helper;
 mod token;
 
-fn expression(ts: &mut TokenStream, variables: &mut VarTable) -> Result<f64> {
-    let mut value = term(ts, variables)?;
+// Pipes bind loosest, so `1 + 15 |> sqrt` applies sqrt to the whole sum
+fn expression(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<Value> {
+    let mut value = fallback(ts, variables, config, warnings)?;
+
+    while let Some(Token::Pipe) = ts.peek()? {
+        ts.next()?;
+        value = match ts.next()? {
+            Some(Token::Name(name)) => match builtins::function(&name) {
+                Some(function) => Value::from(function.call(&[value.to_f64()]).map_err(|error| EvalError::Function(error.to_string()))?),
+                None => anyhow::bail!("Expected a function name after '|>' but '{}' is not a function", name)
+            },
+            Some(token) => anyhow::bail!("Expected a function name after '|>' but got '{}'", token),
+            None => anyhow::bail!("Expected a function name after '|>' but none was found")
+        };
+    }
+
+    Ok(value)
+}
+
+// `a ?? b` is a unless evaluating a fails on an undefined variable or gives NaN, in which case it is b. Other
+// errors are raised as usual. The right operand is always parsed but only evaluated when it is needed.
+fn fallback(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<Value> {
+    let nesting = ts.nesting();
+    let mut start = ts.mark();
+    let mut result = logical(ts, variables, config, warnings);
+
+    loop {
+        if let Err(error) = &result {
+            // While checking, the enclosing check_operand gives undefined names a value and parses again
+            if ts.checking() || !matches!(error.downcast_ref::<EvalError>(), Some(EvalError::UndefinedVariable(_))) {
+                return result;
+            }
+
+            // The failed operand may have stopped anywhere. Its syntax only needs checking when a fallback follows.
+            skip_operand(ts, nesting)?;
+            if ts.peek()? != Some(Token::Fallback) {
+                return result;
+            }
+            ts.reset_to(start);
+            check_operand(ts, variables, config, nesting)?;
+        }
+
+        match ts.peek()? {
+            Some(Token::Fallback) => {
+                ts.next()?;
+                start = ts.mark();
+                if result.as_ref().is_ok_and(|value| !value.to_f64().is_nan()) {
+                    if ts.checking() {
+                        // The variables are already a scratch copy
+                        logical(ts, variables, config, warnings)?;
+                    } else {
+                        check_operand(ts, variables, config, nesting)?;
+                    }
+                } else {
+                    result = logical(ts, variables, config, warnings);
+                }
+            }
+            _ => return result
+        }
+    }
+}
+
+// Parses an operand without evaluating it, so only syntax errors are reported. Undefined names are given a NaN
+// value in a scratch copy of the variables until the operand parses; after a failing function call the rest of
+// the operand is skipped.
+fn check_operand(ts: &mut TokenStream, variables: &VarTable, config: &Config, nesting: isize) -> Result<()> {
+    let mut scratch = variables.clone();
+    let was_checking = ts.set_checking(true);
+
+    let result = loop {
+        let start = ts.mark();
+        let Err(error) = logical(ts, &mut scratch, config, &mut vec![]) else {
+            break Ok(());
+        };
+
+        match error.downcast_ref::<EvalError>() {
+            Some(EvalError::UndefinedVariable(name) | EvalError::NotDefined(name)) => {
+                scratch.store(name, Value::from(f64::NAN));
+                ts.reset_to(start);
+            }
+            Some(EvalError::Function(_)) => {
+                ts.reset_to(start);
+                break skip_operand(ts, nesting);
+            }
+            _ => break Err(error)
+        }
+    };
+
+    ts.set_checking(was_checking);
+    result
+}
+
+// Consumes tokens up to the end of the operand that is open at the given nesting
+fn skip_operand(ts: &mut TokenStream, nesting: isize) -> Result<()> {
+    loop {
+        match ts.peek()? {
+            None | Some(Token::EndStatement) => return Ok(()),
+            Some(Token::Fallback | Token::Pipe | Token::Symbol(',')) if ts.nesting() == nesting => return Ok(()),
+            Some(Token::Symbol(c)) if is_closing_bracket(c) && ts.nesting() == nesting => return Ok(()),
+            _ => { ts.next()?; }
+        }
+    }
+}
+
+// Logical operators bind looser than arithmetic. A value is truthy when it is neither zero nor NaN.
+fn logical(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<Value> {
+    let mut value = arithmetic(ts, variables, config, warnings)?;
+
+    while let Some(Token::LogicalXor) = ts.peek()? {
+        ts.next()?;
+        let rhs = arithmetic(ts, variables, config, warnings)?;
+        value = Value::from(if is_truthy(value.to_f64()) != is_truthy(rhs.to_f64()) { 1.0 } else { 0.0 });
+    }
+
+    Ok(value)
+}
+
+fn is_truthy(value: f64) -> bool {
+    value != 0.0 && !value.is_nan()
+}
+
+// A percentage standing alone as an additive operand is relative to the running value, so
+// `1000 - 20% - 10%` is 1000 -> 800 -> 720. Anywhere else `n%` is just n / 100, e.g. `2 * 50%` is 1.
+fn arithmetic(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<Value> {
+    let (mut value, _) = term(ts, variables, config, warnings)?;
 
     loop {
         match ts.peek()? {
             Some(Token::Symbol('+')) => {
                 ts.next()?;
-                value += term(ts, variables)?;
+                let (rhs, relative) = term(ts, variables, config, warnings)?;
+                value = value + if relative { value * rhs } else { rhs };
             }
             Some(Token::Symbol('-')) => {
                 ts.next()?;
-                value -= term(ts, variables)?;
+                let (rhs, relative) = term(ts, variables, config, warnings)?;
+                value = value - if relative { value * rhs } else { rhs };
             }
             _ => break
         }
@@ -29,54 +171,195 @@ fn expression(ts: &mut TokenStream, variables: &mut VarTable) -> Result<f64> {
     Ok(value)
 }
 
-fn term(ts: &mut TokenStream, variables: &mut VarTable) -> Result<f64> {
-    let mut value = primary(ts, variables)?;
+// Returns the value of the term and whether it consisted of a single percentage like `20%`
+fn term(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<(Value, bool)> {
+    let (mut value, mut is_percentage) = factor(ts, variables, config, warnings)?;
 
     loop {
         match ts.peek()? {
             Some(Token::Symbol('*')) => {
                 ts.next()?;
-                value *= primary(ts, variables)?;
+                value = value * factor(ts, variables, config, warnings)?.0;
             }
             Some(Token::Symbol('/')) => {
                 ts.next()?;
-                value /= primary(ts, variables)?;
+                value = value / factor(ts, variables, config, warnings)?.0;
             }
             _ => break
         }
+        is_percentage = false;
     }
 
-    Ok(value)
+    Ok((value, is_percentage))
+}
+
+fn factor(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<(Value, bool)> {
+    let value = power(ts, variables, config, warnings)?;
+
+    if let Some(Token::Symbol('%')) = ts.peek()? {
+        ts.next()?;
+        return Ok((value / Value::from(100.0), true));
+    }
+
+    Ok((value, false))
+}
+
+// Exponentiation is right associative, 2 ^ 3 ^ 2 is 2 ^ 9
+fn power(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<Value> {
+    let base = primary(ts, variables, config, warnings)?;
+
+    if let Some(Token::Symbol('^')) = ts.peek()? {
+        ts.next()?;
+        return Ok(Value::from(base.to_f64().powf(power(ts, variables, config, warnings)?.to_f64())));
+    }
+
+    Ok(base)
 }
 
-fn primary(ts: &mut TokenStream, variables: &mut VarTable) -> Result<f64> {
+fn primary(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<Value> {
     match ts.next()? {
-        Some(Token::Number(n)) => Ok(n),
-        Some(Token::Symbol('(')) => {
-            let value = expression(ts, variables)?;
+        Some(Token::Number(n)) => Ok(Value::from(n)),
+        Some(Token::Reference(label)) => match variables.retrieve(&label) {
+            Some(value) => Ok(value),
+            None => Err(EvalError::UndefinedVariable(label).into())
+        },
+        Some(Token::Symbol('{')) => block(ts, variables, config, warnings),
+        // There is no indexing, so square brackets only ever group. The closing bracket must match.
+        Some(Token::Symbol(open)) if closing_bracket(open).is_some() => {
+            let close = closing_bracket(open).expect("Should be an opening bracket");
+            let value = expression(ts, variables, config, warnings)?;
             match ts.next()? {
-                Some(Token::Symbol(')')) => Ok(value),
-                _ => anyhow::bail!("Expected closing parenthesis")
+                Some(Token::Symbol(c)) if c == close => Ok(value),
+                Some(Token::Symbol(c)) if is_closing_bracket(c) => anyhow::bail!("Expected '{}' to close '{}' but got '{}'", close, open, c),
+                _ => Err(EvalError::Unclosed(close).into())
             }
         }
         Some(Token::Symbol('-')) => {
-            Ok(-primary(ts, variables)?)
+            // Negation applies after exponentiation, so -2 ^ 2 is -4
+            Ok(-power(ts, variables, config, warnings)?)
         }
         Some(Token::Symbol('+')) => {
-            Ok(primary(ts, variables)?)
+            Ok(primary(ts, variables, config, warnings)?)
+        }
+        Some(Token::Symbol('√')) => {
+            Ok(Value::from(primary(ts, variables, config, warnings)?.to_f64().sqrt()))
+        }
+        Some(Token::Symbol('∛')) => {
+            Ok(Value::from(primary(ts, variables, config, warnings)?.to_f64().cbrt()))
+        }
+        Some(Token::Symbol('∜')) => {
+            Ok(Value::from(primary(ts, variables, config, warnings)?.to_f64().sqrt().sqrt()))
         }
         Some(Token::Name(name)) => {
+            if let Some(function) = builtins::function(&name) {
+                if let Some(Token::Symbol('(')) = ts.peek()? {
+                    ts.next()?;
+                    let args = arguments(ts, variables, config, warnings)?;
+                    return Ok(Value::from(function.call(&args).map_err(|error| EvalError::Function(error.to_string()))?));
+                }
+            }
+
+            if let Some(read) = builtins::text_reader(&name) {
+                if let Some(Token::Symbol('(')) = ts.peek()? {
+                    ts.next()?;
+                    let text = match ts.next()? {
+                        Some(Token::Text(text)) => text,
+                        _ => anyhow::bail!("{0} expects a quoted argument, e.g. '{0}(\"XIV\")'", name)
+                    };
+                    if ts.next()? != Some(Token::Symbol(')')) {
+                        return Err(EvalError::Unclosed(')').into());
+                    }
+                    return Ok(Value::from(read(&text).map_err(|error| EvalError::Function(error.to_string()))?));
+                }
+            }
+
+            if builtins::text_function(&name).is_some() {
+                if let Some(Token::Symbol('(')) = ts.peek()? {
+                    anyhow::bail!("{0} gives text, so it can only be used as a whole statement, e.g. '{0}(42)'", name)
+                }
+            }
+
             if let Some(value) = variables.retrieve(&name) {
                 Ok(value)
+            } else if let Some(value) = builtins::constant(&name) {
+                Ok(Value::from(value))
             } else {
-                anyhow::bail!("Undefined variable: {}", name)
+                Err(EvalError::UndefinedVariable(name).into())
             }
         }
         _ => anyhow::bail!("Expected a number, a variable or an opening parenthesis")
     }
 }
 
-fn statement(ts: &mut TokenStream, variables: &mut VarTable) -> Result<f64> {
+// `{ let t = a + b; t * t }` runs its statements in order and yields the value of the last one. Variables defined
+// inside are dropped at the closing brace, while outer variables stay visible and can be updated with `=`. A `let`
+// inside the block may reuse an outer name, the outer variable is hidden until the block ends.
+// A block holding a single expression is plain grouping: `{2 + 3} * 4` is 20.
+fn block(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<Value> {
+    let scope = variables.scope();
+    let result = block_statements(ts, variables, config, warnings, scope);
+    variables.end_scope(scope);
+    result
+}
+
+fn block_statements(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>, scope: usize) -> Result<Value> {
+    loop {
+        // Empty statements, such as the line breaks of a pasted script, are skipped
+        while let Some(Token::EndStatement) = ts.peek()? {
+            ts.next()?;
+        }
+
+        let value = statement(ts, variables, config, warnings, scope)?;
+
+        match ts.next()? {
+            Some(Token::EndStatement) => {
+                if let Some(Token::Symbol('}')) = ts.peek()? {
+                    ts.next()?;
+                    return Ok(value);
+                }
+            }
+            Some(Token::Symbol('}')) => return Ok(value),
+            Some(Token::Symbol(c)) if is_closing_bracket(c) => anyhow::bail!("Expected '}}' to close '{{' but got '{}'", c),
+            _ => return Err(EvalError::Unclosed('}').into())
+        }
+    }
+}
+
+// Reads a comma-separated list of expressions up to and including the closing parenthesis
+fn arguments(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<Vec<f64>> {
+    let mut args = vec![];
+
+    if let Some(Token::Symbol(')')) = ts.peek()? {
+        ts.next()?;
+        return Ok(args);
+    }
+
+    loop {
+        args.push(expression(ts, variables, config, warnings)?.to_f64());
+
+        match ts.next()? {
+            Some(Token::Symbol(',')) => continue,
+            Some(Token::Symbol(')')) => return Ok(args),
+            None | Some(Token::EndStatement) => return Err(EvalError::Unclosed(')').into()),
+            _ => anyhow::bail!("Expected ',' or closing parenthesis in argument list")
+        }
+    }
+}
+
+// Names that collide with a built-in are still defined, but a warning is reported. A call like `sin(0)` keeps
+// using the built-in function while a bare `sin` reads the variable.
+fn shadowing_warning(label: &str) -> Option<String> {
+    if builtins::function(label).is_some() {
+        Some(format!("'{0}' shadows the built-in function {0}, '{0}(...)' still calls the built-in", label))
+    } else if builtins::constant(label).is_some() {
+        Some(format!("'{0}' shadows the built-in constant {0}", label))
+    } else {
+        None
+    }
+}
+
+// `scope` is where the variables of the enclosing block start, 0 at the top level. Only those can clash with a `let`.
+fn statement(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>, scope: usize) -> Result<Value> {
     match ts.peek()? {
         Some(Token::Let) => {
             ts.next().expect("Should be a let token");
@@ -96,10 +379,12 @@ fn statement(ts: &mut TokenStream, variables: &mut VarTable) -> Result<f64> {
                     _ => anyhow::bail!("Expected a name after let keyword but got '{}'", next_token)
                 };
 
-            if variables.contains(&label) {
-                anyhow::bail!("Variable {} is already defined. Use = to change it's value. Example: 'x = 5'", label)
+            if variables.defined_since(scope, label) {
+                return Err(EvalError::AlreadyDefined(label.clone()).into());
             }
 
+            warnings.extend(shadowing_warning(label));
+
             let next_token = ts.next()?;
 
             if next_token.as_ref().is_some_and(|token| *token != Token::Symbol('=')) {
@@ -107,52 +392,251 @@ fn statement(ts: &mut TokenStream, variables: &mut VarTable) -> Result<f64> {
                 anyhow::bail!("Expected an '=' symbol after 'let {label}' but got '{}'", next_token.unwrap())
             }
 
-            let value = expression(ts, variables)?;
+            let value = stored_value(label, expression(ts, variables, config, warnings)?, config)?;
 
-            variables.store(&label, value);
+            variables.define(label, value);
 
             Ok(value)
         }
+        Some(Token::Set) => {
+            ts.next().expect("Should be a set token");
+
+            // Unlike let, set creates or updates each variable, so it can be run again to reset them
+            loop {
+                let label = match ts.next()? {
+                    Some(Token::Name(name)) => name,
+                    Some(token) => anyhow::bail!("Expected a name in set but got '{}'", token),
+                    None => anyhow::bail!("Expected a name in set but none was found")
+                };
+
+                match ts.next()? {
+                    Some(Token::Symbol('=')) => {}
+                    _ => anyhow::bail!("Expected an '=' symbol after 'set {label}'")
+                }
+
+                warnings.extend(shadowing_warning(&label));
+
+                let value = stored_value(&label, expression(ts, variables, config, warnings)?, config)?;
+                variables.store(&label, value);
+
+                match ts.peek()? {
+                    Some(Token::Symbol(',')) => { ts.next()?; }
+                    _ => return Ok(value)
+                }
+            }
+        }
         Some(Token::Name(label)) => {
             ts.next().expect("Should be a name token");
 
+            // Labels are variables: `total: 2 + 3` creates or updates `total`, which `@total` or plain `total` reads
+            if let Some(Token::Symbol(':')) = ts.peek()? {
+                ts.next().expect("Should be a : token");
+                warnings.extend(shadowing_warning(&label));
+
+                let value = stored_value(&label, expression(ts, variables, config, warnings)?, config)?;
+                variables.store(&label, value);
+                return Ok(value);
+            }
+
             if let Some(Token::Symbol('=')) = ts.peek()? {
                 ts.next().expect("Should be an = token");
 
                 if !variables.contains(&label) {
-                    anyhow::bail!("Variable {} is not defined. Use let to define it before assigning a value. Example: 'let {} = 5; x'", label, label)
+                    return Err(EvalError::NotDefined(label).into());
                 }
 
-                let value = expression(ts, variables)?;
+                let value = stored_value(&label, expression(ts, variables, config, warnings)?, config)?;
                 variables.store(&label, value);
                 Ok(value)
             } else {
-                let val = variables.retrieve(&label).ok_or_else(|| anyhow!("Undefined variable: {}", label))?;
-                ts.put_back(Token::Number(val)); // Now the variable value occupies the same place as it's label did
-                expression(ts, variables)
+                ts.put_back(Token::Name(label)); // Let primary resolve the name as a variable, constant or function call
+                expression(ts, variables, config, warnings)
             }
         }
-        _ => expression(ts, variables)
+        _ => expression(ts, variables, config, warnings)
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum EvaluationResult {
+// Without a separator the next operand would start a new statement, so `5 3` gives 5 and 3. In strict mode that is an
+// error instead, since it is usually a typo.
+fn check_operator_follows(ts: &mut TokenStream, config: &Config) -> Result<()> {
+    if !config.require_operators {
+        return Ok(());
+    }
+
+    match ts.peek()? {
+        Some(Token::Number(_) | Token::Name(_) | Token::Reference(_) | Token::Symbol('√' | '∛' | '∜')) => anyhow::bail!("Missing operator between operands"),
+        Some(Token::Symbol(c)) if closing_bracket(c).is_some() => anyhow::bail!("Missing operator between operands"),
+        _ => Ok(())
+    }
+}
+
+// Values are stored with the configured number of decimal places, while intermediate results keep full precision.
+// Values too large to scale already have fewer decimals than asked for and are stored as they are.
+// NaN and infinities can be refused so they don't silently spread to everything that uses the variable.
+fn stored_value(label: &str, value: Value, config: &Config) -> Result<Value> {
+    let float = value.to_f64();
+    if config.reject_non_finite && !float.is_finite() {
+        return Err(EvalError::NonFinite(label.to_string(), float).into());
+    }
+
+    Ok(match config.storage_precision {
+        Some(places) => {
+            let scale = 10f64.powf(places as f64);
+            let scaled = float * scale;
+            if scaled.is_finite() { Value::from(scaled.round() / scale) } else { value }
+        }
+        None => value
+    })
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+enum EvalError {
+    #[error("Undefined variable: {0}")]
+    UndefinedVariable(String),
+    #[error("Expected closing '{0}'")]
+    Unclosed(char),
+    #[error("Variable {0} is already defined. Use = to change it's value. Example: 'x = 5'")]
+    AlreadyDefined(String),
+    #[error("Refusing to store {1} in {0}")]
+    NonFinite(String, f64),
+    #[error("Variable {0} is not defined. Use let to define it before assigning a value. Example: 'let {0} = 5; x'")]
+    NotDefined(String),
+    #[error("{0}")]
+    Function(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Tokenization,
+    UndefinedVariable,
+    Evaluation,
+    Truncated,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct EvaluationError {
+    kind: ErrorKind,
+    message: String,
+    // The underlying evaluation error, kept so the REPL can suggest a fix
+    #[serde(skip)]
+    cause: Option<EvalError>,
+}
+
+impl EvaluationError {
+    fn new(kind: ErrorKind, message: String) -> EvaluationError {
+        EvaluationError { kind, message, cause: None }
+    }
+
+    fn from_statement(token: &Token, error: anyhow::Error) -> EvaluationError {
+        let kind = if error.downcast_ref::<TokenizationError>().is_some() {
+            ErrorKind::Tokenization
+        } else if let Some(EvalError::UndefinedVariable(_)) = error.downcast_ref::<EvalError>() {
+            ErrorKind::UndefinedVariable
+        } else {
+            ErrorKind::Evaluation
+        };
+
+        EvaluationError {
+            cause: error.downcast_ref::<EvalError>().cloned(),
+            ..EvaluationError::new(kind, format!("Error occurred while evaluating '{}': {}", token, error))
+        }
+    }
+}
+
+impl EvaluationError {
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+}
+
+impl Display for EvaluationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvaluationResult {
+    #[serde(serialize_with = "serialize_number")]
     Number(f64),
-    Error(String),
+    Text(String),
+    Error(EvaluationError),
+    Warning(String),
     Quit,
 }
 
+// JSON has no NaN or infinities, so those are written as the strings "NaN", "inf" and "-inf" instead of null
+fn serialize_number<S: serde::Serializer>(value: &f64, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    if value.is_finite() {
+        serializer.serialize_f64(*value)
+    } else {
+        serializer.serialize_str(&value.to_string())
+    }
+}
+
+impl Display for EvaluationResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvaluationResult::Number(n) => write!(f, "{}", n),
+            EvaluationResult::Text(text) => write!(f, "{}", text),
+            EvaluationResult::Error(e) => write!(f, "error: {}", e),
+            EvaluationResult::Warning(w) => write!(f, "warning: {}", w),
+            EvaluationResult::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+// A call to a text built-in such as `to_words(42)` makes up a whole statement, anything else is a number
+fn statement_result(ts: &mut TokenStream, variables: &mut VarTable, config: &Config, warnings: &mut Vec<String>) -> Result<EvaluationResult> {
+    if let Some(Token::Name(name)) = ts.peek()? {
+        if let Some(function) = builtins::text_function(&name) {
+            ts.next()?;
+
+            if let Some(Token::Symbol('(')) = ts.peek()? {
+                ts.next()?;
+                let args = arguments(ts, variables, config, warnings)?;
+                let text = function.call(&args).map_err(|error| EvalError::Function(error.to_string()))?;
+
+                if let Some(Token::Symbol(_) | Token::Pipe | Token::Fallback | Token::LogicalXor) = ts.peek()? {
+                    anyhow::bail!("{0} gives text, so it can only be used as a whole statement, e.g. '{0}(42)'", name)
+                }
+
+                return Ok(EvaluationResult::Text(text));
+            }
+
+            ts.put_back(Token::Name(name));
+        }
+    }
+
+    Ok(EvaluationResult::Number(statement(ts, variables, config, warnings, 0)?.to_f64()))
+}
+
+#[cfg(test)]
 fn evaluate(expression: &str, variables: &mut VarTable) -> Vec<EvaluationResult> {
-    let mut ts = TokenStream::new(expression.as_bytes());
+    evaluate_with_config(expression, variables, &Config::default())
+}
+
+fn evaluate_with_config(expression: &str, variables: &mut VarTable, config: &Config) -> Vec<EvaluationResult> {
+    let mut ts = TokenStream::with_config(expression, config);
     let mut val: Option<f64> = None;
     let mut res = vec![];
 
     loop {
+        if let Some(max) = config.max_results.filter(|max| res.len() >= *max) {
+            if ts.peek().is_ok_and(|token| token.is_some()) {
+                res.push(truncation_marker(max));
+            }
+            break;
+        }
+
         let token =
             ts.peek()
                 .map_err(|e| {
-                    res.push(EvaluationResult::Error(format!("Error occurred while peeking next token: {}", e)));
+                    let message = format!("Error occurred while peeking next token: {}", e);
+                    res.push(EvaluationResult::Error(EvaluationError::new(ErrorKind::Tokenization, message)));
                     ts.discard_invalid();
                 })
                 .ok()
@@ -169,12 +653,23 @@ fn evaluate(expression: &str, variables: &mut VarTable) -> Vec<EvaluationResult>
                 ts.next().expect("Should have a quit token in the stream");
             }
             Some(token) => {
-                statement(&mut ts, variables)
-                    .map(|result| res.push(EvaluationResult::Number(result)))
+                let mut warnings = vec![];
+                let result = statement_result(&mut ts, variables, config, &mut warnings)
+                    .and_then(|value| check_operator_follows(&mut ts, config).map(|_| value));
+                res.extend(warnings.into_iter().map(EvaluationResult::Warning));
+                result
+                    .map(|result| res.push(result))
                     .unwrap_or_else(|e| {
-                        res.push(EvaluationResult::Error(format!("Error occurred while evaluating '{}': {}", token, e)));
+                        res.push(EvaluationResult::Error(EvaluationError::from_statement(&token, e)));
                         ts.discard_invalid();
                     });
+
+                // A single statement can add several warnings, they count towards the cap too
+                if let Some(max) = config.max_results.filter(|max| res.len() > *max) {
+                    res.truncate(max);
+                    res.push(truncation_marker(max));
+                    break;
+                }
             }
             None => {
                 if let Some(val) = val { res.push(EvaluationResult::Number(val)) }
@@ -186,16 +681,28 @@ fn evaluate(expression: &str, variables: &mut VarTable) -> Vec<EvaluationResult>
     res
 }
 
+fn truncation_marker(max: usize) -> EvaluationResult {
+    let message = format!("Output truncated after {} results", max);
+    EvaluationResult::Error(EvaluationError::new(ErrorKind::Truncated, message))
+}
+
+#[derive(Clone)]
 struct Variable {
     label: String,
-    value: f64,
+    value: Value,
 }
 
-struct VarTable(Vec<Variable>);
+#[derive(Clone)]
+pub struct VarTable(Vec<Variable>);
 
 impl VarTable {
-    fn store(&mut self, label: &String, value: f64) {
-        for var in self.0.iter_mut() {
+    pub fn new() -> VarTable {
+        VarTable(vec![])
+    }
+
+    // Updates the innermost variable with the label, so a block-local variable hides an outer one
+    fn store(&mut self, label: &String, value: Value) {
+        for var in self.0.iter_mut().rev() {
             if *var.label == *label {
                 var.value = value;
                 return;
@@ -205,16 +712,35 @@ impl VarTable {
         self.0.push(Variable { label: label.clone(), value });
     }
 
+    // Variables are kept in definition order, so a scope is just the number of variables defined when it started
+    // Adds a new variable even when an outer scope has one with the same label
+    fn define(&mut self, label: &str, value: Value) {
+        self.0.push(Variable { label: label.to_string(), value });
+    }
+
+    fn scope(&self) -> usize {
+        self.0.len()
+    }
+
+    // Drops the variables defined since the scope started
+    fn end_scope(&mut self, scope: usize) {
+        self.0.truncate(scope);
+    }
+
     fn contains(&self, label: &String) -> bool {
         for var in self.0.iter() {
             if var.label == *label { return true; }
         }
 
-        return false;
+        false
     }
 
-    fn retrieve(&self, label: &String) -> Option<f64> {
-        for var in self.0.iter() {
+    fn defined_since(&self, scope: usize, label: &str) -> bool {
+        self.0[scope..].iter().any(|var| var.label == *label)
+    }
+
+    fn retrieve(&self, label: &String) -> Option<Value> {
+        for var in self.0.iter().rev() {
             if *var.label == *label { return Some(var.value); }
         }
 
@@ -222,87 +748,416 @@ impl VarTable {
     }
 }
 
-pub fn calculate() {
-    let mut input: String;
-    let mut should_quit = false;
-    let mut variables: VarTable = VarTable(vec![]);
+impl Default for VarTable {
+    fn default() -> Self {
+        VarTable::new()
+    }
+}
 
-    let mut prompter = DefaultEditor::new()
-        .unwrap_or_else(|e| panic!("Failed to create prompter: {}", e));
+// Tentative value of the input as it is being typed: the longest prefix ending on a token boundary that forms a complete
+// expression is evaluated, and anything after it is ignored. So `2 + 3 *` gives 5, while `(2 + 3` gives None because no
+// prefix closes the parenthesis. Only the first statement is considered and the variables are never modified.
+pub fn eval_prefix(input: &str, variables: &VarTable) -> Option<f64> {
+    let mut ts = TokenStream::from_chars(input);
+    let mut boundaries = vec![];
+
+    while let Ok(Some(token)) = ts.next() {
+        // Separators inside a block belong to it, only one at the top level ends the statement
+        if matches!(token, Token::EndStatement | Token::Quit) && ts.nesting() <= 0 {
+            break;
+        }
+        boundaries.push(ts.position());
+    }
 
-    loop {
-        input = match prompter.readline("> ") {
-            Ok(line) => line,
-            Err(ReadlineError::Interrupted) => continue,
-            Err(ReadlineError::Eof) => break,
-            Err(err) => {
-                println!("Error occurred while reading input: {}", err);
-                break;
-            }
-        };
+    boundaries.into_iter().rev().find_map(|end| {
+        let prefix: String = input.chars().take(end).collect();
+        let mut ts = TokenStream::from_chars(&prefix);
+        let value = expression(&mut ts, &mut variables.clone(), &Config::default(), &mut vec![]).ok()?;
+        ts.peek().ok()?.is_none().then_some(value.to_f64())
+    })
+}
 
-        for result in evaluate(input.as_str(), &mut variables) {
-            match result {
-                EvaluationResult::Number(n) => println!("={}", n),
-                EvaluationResult::Error(e) => eprintln!("{}", e),
-                EvaluationResult::Quit => should_quit = true
-            }
-        }
+// Evaluates pasted multi-line input against fresh variables. Newlines separate statements just like `;` does.
+pub fn evaluate_script(input: &str) -> (Vec<EvaluationResult>, VarTable) {
+    let mut variables = VarTable::new();
+    let results = evaluate_with_config(&input.replace('\n', ";"), &mut variables, &Config::default());
+    (results, variables)
+}
 
-        if should_quit { exit(0) }
-    }
+// Evaluates the input like the REPL would and serializes the results, e.g. `[{"number":8.0}]`. NaN and infinities
+// are the strings "NaN", "inf" and "-inf". Errors carry a kind and a message:
+// `{"error":{"kind":"undefined_variable","message":"..."}}`.
+pub fn evaluate_json(input: &str, variables: &mut VarTable) -> String {
+    let results = evaluate_with_config(input, variables, &Config::default());
+    serde_json::to_string(&results).expect("Evaluation results should always serialize")
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+pub struct ReplState {
+    variables: VarTable,
+    config: Config,
+    // Smallest and largest results of the session, NaN results are ignored
+    min: Option<f64>,
+    max: Option<f64>,
+    // Named sources recorded with `macro <name> = <body>` and replayed with `run <name>`
+    macros: Vec<(String, String)>,
+    // Macros currently being replayed, used to stop a macro from running itself
+    running_macros: Vec<String>,
+}
 
-    #[test]
-    fn test_evaluate_with_spaces() {
-        let mut variables = VarTable(vec![]);
-        let result = evaluate("5 + 3", &mut variables);
-        assert_eq!(result, vec![EvaluationResult::Number(8.0)]);
+impl ReplState {
+    pub fn new() -> ReplState {
+        ReplState::with_config(Config::default())
     }
 
-    #[test]
-    fn test_evaluate_without_spaces() {
-        let mut variables = VarTable(vec![]);
-        let result = evaluate("5+3", &mut variables);
-        assert_eq!(result, vec![EvaluationResult::Number(8.0)]);
+    pub fn with_config(config: Config) -> ReplState {
+        ReplState { variables: VarTable(vec![]), config, min: None, max: None, macros: vec![], running_macros: vec![] }
     }
 
-    #[test]
-    fn test_evaluate_with_multiple_spaces() {
-        let mut variables = VarTable(vec![]);
-        let result = evaluate("5   +   3", &mut variables);
-        assert_eq!(result, vec![EvaluationResult::Number(8.0)]);
+    fn track_extremes(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
     }
+}
 
-    #[test]
-    fn test_evaluate_with_invalid_expression() {
-        let mut variables = VarTable(vec![]);
-        let result = evaluate("5 + * 3", &mut variables);
-        assert!(
-            matches!(result[0], EvaluationResult::Error(_)),
-        )
+impl Default for ReplState {
+    fn default() -> Self {
+        ReplState::new()
     }
+}
 
-    #[test]
-    fn test_evaluate_with_variable() {
-        let mut variables = VarTable(vec![Variable { label: "x".to_string(), value: 5.0 }]);
-        let result = evaluate("x + 3", &mut variables);
-        assert_eq!(result, vec![EvaluationResult::Number(8.0)]);
+#[derive(Debug, PartialEq)]
+pub enum ReplOutput {
+    Print(String),
+    Error(String),
+    Warning(String),
+    Quit,
+}
+
+// Processes a single line of input against the session state, without touching stdin or stdout
+pub fn repl_once(input: &str, state: &mut ReplState) -> Vec<ReplOutput> {
+    if let Some(outputs) = repl_command(input, state) {
+        return outputs;
     }
 
-    #[test]
-    fn test_evaluate_with_undefined_variable() {
-        let mut variables = VarTable(vec![]);
-        let result = evaluate("x + 3", &mut variables);
-        assert_eq!(result.len(), 1, "Result should contain only one element");
-        assert!(
-            matches!(result[0], EvaluationResult::Error(_)),
-            "Result should be an error for expression 'x + 3'"
-        )
+    let results = evaluate_with_config(input, &mut state.variables, &state.config);
+
+    for result in results.iter() {
+        if let EvaluationResult::Number(n) = result {
+            state.track_extremes(*n);
+        }
+    }
+
+    results.into_iter().filter_map(|result| repl_output(result, state)).collect()
+}
+
+fn repl_output(result: EvaluationResult, state: &ReplState) -> Option<ReplOutput> {
+    match result {
+        EvaluationResult::Number(_) | EvaluationResult::Text(_) if state.config.silent => None,
+        // Numbers go through the configurable formatter rather than the plain Display
+        EvaluationResult::Number(n) => Some(ReplOutput::Print(format!("={}", format::format_result(n, &state.config)))),
+        EvaluationResult::Text(text) => Some(ReplOutput::Print(format!("={}", text))),
+        EvaluationResult::Error(ref e) => Some(ReplOutput::Error(describe_error(e, &state.variables).unwrap_or_else(|| result.to_string()))),
+        EvaluationResult::Warning(_) => Some(ReplOutput::Warning(result.to_string())),
+        EvaluationResult::Quit => Some(ReplOutput::Quit)
+    }
+}
+
+// Appends a suggested fix to common errors, e.g. the closest defined name for an undefined variable
+fn describe_error(error: &EvaluationError, variables: &VarTable) -> Option<String> {
+    let suggestion = match error.cause.as_ref()? {
+        EvalError::UndefinedVariable(name) => match closest_name(name, variables) {
+            Some(closest) => format!("did you mean `{}`?", closest),
+            None => format!("did you mean to use `let {} = ...` first?", name)
+        },
+        EvalError::NotDefined(name) => format!("did you mean `{}`?", closest_name(name, variables)?),
+        EvalError::Unclosed(close) => format!("add a closing `{}`", close),
+        EvalError::NonFinite(name, _) => format!("use `??` to store a fallback, e.g. `{} = (0/0) ?? 0`", name),
+        EvalError::AlreadyDefined(name) => format!("use `set {} = ...` to define or update it", name),
+        EvalError::Function(_) => return None,
+    };
+
+    Some(format!("error: {} ({})", error, suggestion))
+}
+
+// Defined variables and constants within a small edit distance of the name, closest first
+fn closest_name<'a>(name: &str, variables: &'a VarTable) -> Option<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    variables.0.iter()
+        .map(|var| var.label.as_str())
+        .chain(builtins::constants())
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        // Replacing every character isn't a typo, so `y` doesn't suggest `π`
+        .filter(|(distance, candidate)| *distance <= max_distance && *distance < name.chars().count().max(candidate.chars().count()))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// Levenshtein distance counted in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// Commands that only make sense in the REPL are handled before the input reaches the evaluator
+fn repl_command(input: &str, state: &mut ReplState) -> Option<Vec<ReplOutput>> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+
+    match words[..] {
+        ["silent", "on"] => state.config.silent = true,
+        ["silent", "off"] => state.config.silent = false,
+        ["notation", "plain"] => state.config.notation = Notation::Plain,
+        ["notation", "sci"] => state.config.notation = Notation::Scientific,
+        ["notation", "eng"] => state.config.notation = Notation::Engineering,
+        ["extremes"] => {
+            let output = match (state.min, state.max) {
+                (Some(min), Some(max)) => format!(
+                    "min={} max={}",
+                    format::format_result(min, &state.config),
+                    format::format_result(max, &state.config)
+                ),
+                _ => "No results yet".to_string()
+            };
+            return Some(vec![ReplOutput::Print(output)]);
+        }
+        ["vars"] => {
+            let rows: Vec<(String, String)> = state.variables.0.iter()
+                .map(|var| (var.label.clone(), format::format_result(var.value.to_f64(), &state.config)))
+                .collect();
+            let output = if rows.is_empty() { "No variables defined".to_string() } else { format::format_table(&rows) };
+            return Some(vec![ReplOutput::Print(output)]);
+        }
+        ["funcs"] => {
+            let rows: Vec<(String, String)> = builtins::functions()
+                .map(|function| (function.name.to_string(), format!("{} argument(s)", function.arity)))
+                .chain(builtins::text_functions()
+                    .map(|function| (function.name.to_string(), format!("{} argument(s), gives text", function.arity))))
+                .chain(builtins::text_readers().map(|name| (name.to_string(), "1 quoted argument".to_string())))
+                .collect();
+            return Some(vec![ReplOutput::Print(format::format_table(&rows))]);
+        }
+        ["extremes", "reset"] => {
+            state.min = None;
+            state.max = None;
+        }
+        ["macro", ..] => return Some(define_macro(input, state)),
+        ["run", name] => return Some(run_macro(name, state)),
+        ["bench", count, _, ..] => return Some(bench(count, input, state)),
+        _ => return None
+    }
+
+    Some(vec![])
+}
+
+// Kept as running totals rather than every sample, so any number of runs takes the same memory
+#[derive(Debug, PartialEq)]
+struct TimingStats {
+    runs: u32,
+    min: Duration,
+    total: Duration,
+    max: Duration,
+}
+
+impl TimingStats {
+    fn new() -> TimingStats {
+        TimingStats { runs: 0, min: Duration::MAX, total: Duration::ZERO, max: Duration::ZERO }
+    }
+
+    fn record(&mut self, sample: Duration) {
+        self.runs += 1;
+        self.min = self.min.min(sample);
+        self.total = self.total.saturating_add(sample);
+        self.max = self.max.max(sample);
+    }
+
+    fn mean(&self) -> Option<Duration> {
+        (self.runs > 0).then(|| self.total / self.runs)
+    }
+}
+
+// `bench 1000 expr` evaluates expr the given number of times and reports its results once, followed by timing
+// statistics. Every run starts from the session variables, so assignments in expr don't accumulate. A `q` in expr
+// is not forwarded, benchmarking never ends the session.
+fn bench(count: &str, input: &str, state: &mut ReplState) -> Vec<ReplOutput> {
+    let runs = match count.parse::<u32>() {
+        Ok(runs) if runs > 0 => runs,
+        _ => return vec![ReplOutput::Error(format!("error: Expected a positive number of runs but got '{}'", count))]
+    };
+    let expression = input.trim_start()["bench".len()..].trim_start()[count.len()..].trim();
+
+    let mut stats = TimingStats::new();
+    let mut results = vec![];
+    for _ in 0..runs {
+        let start = Instant::now();
+        results = evaluate_with_config(expression, &mut state.variables.clone(), &state.config);
+        stats.record(start.elapsed());
+    }
+
+    let mut outputs: Vec<ReplOutput> = results.into_iter()
+        .filter(|result| *result != EvaluationResult::Quit)
+        .filter_map(|result| repl_output(result, state))
+        .collect();
+
+    let mean = stats.mean().expect("Should have at least one run");
+    outputs.push(ReplOutput::Print(format!("{} runs: min {:?}, mean {:?}, max {:?}", runs, stats.min, mean, stats.max)));
+    outputs
+}
+
+// The body is everything after the first `=`, kept as typed so it can be replayed like any other input
+fn define_macro(input: &str, state: &mut ReplState) -> Vec<ReplOutput> {
+    let definition = input.trim_start().strip_prefix("macro").expect("Should start with macro");
+
+    match definition.split_once('=').map(|(name, body)| (name.trim(), body.trim())) {
+        Some((name, body)) if is_macro_name(name) && !body.is_empty() => {
+            state.macros.retain(|(label, _)| label != name);
+            state.macros.push((name.to_string(), body.to_string()));
+            vec![]
+        }
+        _ => vec![ReplOutput::Error("error: Expected a macro definition like 'macro inc = x = x + 1'".to_string())]
+    }
+}
+
+fn is_macro_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// Replays the body against the session state, so a macro may itself be a `run` of another macro
+fn run_macro(name: &str, state: &mut ReplState) -> Vec<ReplOutput> {
+    let body = match state.macros.iter().find(|(label, _)| label == name) {
+        Some((_, body)) => body.clone(),
+        None => return vec![ReplOutput::Error(format!("error: Macro {} is not defined", name))]
+    };
+
+    if state.running_macros.iter().any(|running| running == name) {
+        return vec![ReplOutput::Error(format!("error: Macro {} runs itself", name))];
+    }
+
+    state.running_macros.push(name.to_string());
+    let outputs = repl_once(&body, state);
+    state.running_macros.pop();
+    outputs
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Invocation {
+    Help,
+    Version,
+    // Arguments that aren't flags are joined into a single expression, so `ppp_calculator 1 + 1` works unquoted
+    Evaluate(String),
+    UnknownFlag(String),
+    Repl,
+}
+
+// Decides what the binary should do from its arguments, excluding the program name. Only arguments starting with `--`
+// or exactly `-h`/`-V` are flags, so negative numbers like `-5 + 1` are still evaluated.
+pub fn classify_args(args: &[String]) -> Invocation {
+    match args.first().map(String::as_str) {
+        None => Invocation::Repl,
+        Some("--help" | "-h") => Invocation::Help,
+        Some("--version" | "-V") => Invocation::Version,
+        Some(flag) if flag.starts_with("--") => Invocation::UnknownFlag(flag.to_string()),
+        Some(_) => Invocation::Evaluate(args.join(" ")),
+    }
+}
+
+pub fn calculate() {
+    let mut input: String;
+    let mut should_quit = false;
+    let mut state = ReplState::new();
+
+    let mut prompter: Editor<PromptHelper, DefaultHistory> = Editor::new()
+        .unwrap_or_else(|e| panic!("Failed to create prompter: {}", e));
+    prompter.set_helper(Some(PromptHelper::default()));
+
+    loop {
+        input = match prompter.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                println!("Error occurred while reading input: {}", err);
+                break;
+            }
+        };
+
+        for output in repl_once(input.as_str(), &mut state) {
+            match output {
+                ReplOutput::Print(text) => println!("{}", text),
+                ReplOutput::Error(e) => eprintln!("{}", e),
+                ReplOutput::Warning(w) => eprintln!("{}", w),
+                ReplOutput::Quit => should_quit = true
+            }
+        }
+
+        if should_quit { exit(0) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_with_spaces() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("5 + 3", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(8.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_without_spaces() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("5+3", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(8.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_with_multiple_spaces() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("5   +   3", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(8.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_with_invalid_expression() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("5 + * 3", &mut variables);
+        assert!(
+            matches!(result[0], EvaluationResult::Error(_)),
+        )
+    }
+
+    #[test]
+    fn test_evaluate_with_variable() {
+        let mut variables = VarTable(vec![Variable { label: "x".to_string(), value: Value::from(5.0) }]);
+        let result = evaluate("x + 3", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(8.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_with_undefined_variable() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("x + 3", &mut variables);
+        assert_eq!(result.len(), 1, "Result should contain only one element");
+        assert!(
+            matches!(result[0], EvaluationResult::Error(_)),
+            "Result should be an error for expression 'x + 3'"
+        )
     }
 
     #[test]
@@ -310,7 +1165,7 @@ mod tests {
         let mut variables = VarTable(vec![]);
         let result = evaluate("let x = 5", &mut variables);
         assert_eq!(result, vec![EvaluationResult::Number(5.0)], "Result should be 5 for expression 'let x = 5'");
-        assert_eq!(variables.retrieve(&"x".to_string()), Some(5.0), "Variable x should be 5 after assignment");
+        assert_eq!(variables.retrieve(&"x".to_string()).map(Value::to_f64), Some(5.0), "Variable x should be 5 after assignment");
     }
 
     #[test]
@@ -373,7 +1228,7 @@ mod tests {
 
     #[test]
     fn test_evaluate_with_complex_expression() {
-        let mut variables = VarTable(vec![Variable { label: "y".to_string(), value: 2.0 }]);
+        let mut variables = VarTable(vec![Variable { label: "y".to_string(), value: Value::from(2.0) }]);
         let result = evaluate("3 * (2 + y) / 2", &mut variables);
         assert_eq!(result, vec![EvaluationResult::Number(6.0)], "Complex expression with variable should be evaluated correctly");
     }
@@ -425,7 +1280,7 @@ mod tests {
 
     #[test]
     fn test_evaluate_with_let_and_already_defined_variable() {
-        let mut variables = VarTable(vec![Variable { label: "x".to_string(), value: 5.0 }]);
+        let mut variables = VarTable(vec![Variable { label: "x".to_string(), value: Value::from(5.0) }]);
         let result = evaluate("let x = 10; x + 3", &mut variables);
         assert_eq!(result.len(), 2, "Result should contain exactly two elements");
         assert!(
@@ -476,4 +1331,1010 @@ mod tests {
             "Assign, use, change, use should be evaluated correctly"
         );
     }
+
+    #[test]
+    fn test_evaluate_nanmax_skips_nan() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("nanmax(1, nan, 3)", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(3.0)], "nanmax should ignore NaN arguments");
+    }
+
+    #[test]
+    fn test_evaluate_nanmin_skips_nan() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("nanmin(nan, 4, 2)", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(2.0)], "nanmin should ignore NaN arguments");
+    }
+
+    #[test]
+    fn test_evaluate_max_propagates_nan() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("max(1, nan, 3)", &mut variables);
+        assert!(matches!(result[..], [EvaluationResult::Number(n)] if n.is_nan()), "max should propagate NaN");
+    }
+
+    #[test]
+    fn test_evaluate_nanmax_all_nan() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("nanmax(nan, nan)", &mut variables);
+        assert!(matches!(result[..], [EvaluationResult::Number(n)] if n.is_nan()), "nanmax of only NaN should be NaN");
+    }
+
+    #[test]
+    fn test_evaluate_function_with_expression_arguments() {
+        let mut variables = VarTable(vec![Variable { label: "x".to_string(), value: Value::from(5.0) }]);
+        let result = evaluate("2 * nanmax(x + 1, 3)", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(12.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_logical_xor() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("1 ^^ 0", &mut variables), vec![EvaluationResult::Number(1.0)]);
+        assert_eq!(evaluate("1 ^^ 1", &mut variables), vec![EvaluationResult::Number(0.0)]);
+        assert_eq!(evaluate("0 ^^ 0", &mut variables), vec![EvaluationResult::Number(0.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_logical_xor_binds_looser_than_arithmetic() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("2 - 2 ^^ 3 * 0", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(0.0)], "Both operands should be evaluated before the xor");
+    }
+
+    #[test]
+    fn test_repl_once_keeps_state_between_calls() {
+        let mut state = ReplState::new();
+        assert_eq!(repl_once("let x = 5", &mut state), vec![ReplOutput::Print("=5".to_string())]);
+        assert_eq!(repl_once("x * 2", &mut state), vec![ReplOutput::Print("=10".to_string())]);
+        assert_eq!(repl_once("x = 1; x + 1", &mut state), vec![ReplOutput::Print("=1".to_string()), ReplOutput::Print("=2".to_string())]);
+    }
+
+    #[test]
+    fn test_repl_once_reports_errors_and_quit() {
+        let mut state = ReplState::new();
+        let outputs = repl_once("y + 1; q", &mut state);
+        assert_eq!(outputs.len(), 2, "Outputs should contain an error and a quit");
+        assert!(matches!(outputs[0], ReplOutput::Error(_)));
+        assert_eq!(outputs[1], ReplOutput::Quit);
+    }
+
+    #[test]
+    fn test_evaluate_with_adjacent_signs() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("5 +- 3", &mut variables), vec![EvaluationResult::Number(2.0)], "'5 +- 3' should parse as 5 + (-3)");
+        assert_eq!(evaluate("5 -- 3", &mut variables), vec![EvaluationResult::Number(8.0)], "'5 -- 3' should parse as 5 - (-3)");
+        assert_eq!(evaluate("5 *- 3", &mut variables), vec![EvaluationResult::Number(-15.0)], "'5 *- 3' should parse as 5 * (-3)");
+        assert_eq!(evaluate("5 -+- 3", &mut variables), vec![EvaluationResult::Number(8.0)], "Chained signs should all be unary");
+    }
+
+    #[test]
+    fn test_evaluate_truncates_results_at_configured_cap() {
+        let mut variables = VarTable(vec![]);
+        let config = Config { max_results: Some(3), ..Config::default() };
+        let input = vec!["1"; 1000].join(";");
+        let result = evaluate_with_config(&input, &mut variables, &config);
+        assert_eq!(result.len(), 4, "Result should hold the capped results plus a truncation marker");
+        assert_eq!(result[..3], [EvaluationResult::Number(1.0), EvaluationResult::Number(1.0), EvaluationResult::Number(1.0)]);
+        assert!(matches!(result[3], EvaluationResult::Error(_)), "Last result should be the truncation marker");
+    }
+
+    #[test]
+    fn test_evaluate_truncates_warnings_at_configured_cap() {
+        let mut variables = VarTable(vec![]);
+        let config = Config { max_results: Some(1), ..Config::default() };
+        let result = evaluate_with_config("set sin=1, cos=2, tan=3, pi=4", &mut variables, &config);
+        assert_eq!(result.len(), 2, "Result should hold the capped warning plus a truncation marker");
+        assert!(matches!(result[0], EvaluationResult::Warning(_)));
+        assert!(matches!(&result[1], EvaluationResult::Error(e) if e.kind() == ErrorKind::Truncated));
+    }
+
+    #[test]
+    fn test_evaluate_does_not_truncate_at_exact_cap() {
+        let mut variables = VarTable(vec![]);
+        let config = Config { max_results: Some(2), ..Config::default() };
+        let result = evaluate_with_config("1; 2", &mut variables, &config);
+        assert_eq!(result, vec![EvaluationResult::Number(1.0), EvaluationResult::Number(2.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_pipe() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("16 |> sqrt", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(4.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_chained_pipe() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("-16 |> abs |> sqrt", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(4.0)], "Pipes should apply left to right");
+    }
+
+    #[test]
+    fn test_evaluate_pipe_binds_loosest() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("1 + 15 |> sqrt", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(4.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_pipe_into_non_function() {
+        let mut variables = VarTable(vec![Variable { label: "x".to_string(), value: Value::from(5.0) }]);
+        let result = evaluate("16 |> x", &mut variables);
+        assert!(matches!(result[..], [EvaluationResult::Error(_)]), "Piping into a variable should be an error");
+    }
+
+    #[test]
+    fn test_evaluate_leading_zero_in_strict_mode() {
+        let mut variables = VarTable(vec![]);
+        let config = Config { strict_leading_zeros: true, ..Config::default() };
+        let result = evaluate_with_config("007", &mut variables, &config);
+        assert!(matches!(result[..], [EvaluationResult::Error(_)]), "'007' should be rejected in strict mode");
+        let result = evaluate_with_config("0 + 0.5", &mut variables, &config);
+        assert_eq!(result, vec![EvaluationResult::Number(0.5)], "'0' and '0.5' should be accepted in strict mode");
+    }
+
+    #[test]
+    fn test_evaluate_leading_zero_in_lenient_mode() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("007", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(7.0)]);
+    }
+
+    #[test]
+    fn test_repl_once_clamps_display_but_not_stored_value() {
+        let mut state = ReplState::with_config(Config { display_clamp: Some((0.0, 100.0)), ..Config::default() });
+        assert_eq!(repl_once("let x = 150", &mut state), vec![ReplOutput::Print("=100".to_string())]);
+        assert_eq!(repl_once("x - 100", &mut state), vec![ReplOutput::Print("=50".to_string())]);
+    }
+
+    #[test]
+    fn test_evaluate_percentage() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("50%", &mut variables), vec![EvaluationResult::Number(0.5)]);
+        assert_eq!(evaluate("2 * 50%", &mut variables), vec![EvaluationResult::Number(1.0)]);
+        assert_eq!(evaluate("50% * 2", &mut variables), vec![EvaluationResult::Number(1.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_percentage_chain() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("1000 - 20% - 10%", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(720.0)], "Each percentage should apply to the running value");
+    }
+
+    #[test]
+    fn test_evaluate_percentage_increase() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("200 + 10% + 5", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(225.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_wrap() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("wrap(370, 360)", &mut variables), vec![EvaluationResult::Number(10.0)]);
+        assert_eq!(evaluate("wrap(-10, 360)", &mut variables), vec![EvaluationResult::Number(350.0)]);
+        assert_eq!(evaluate("wrap(720, 360)", &mut variables), vec![EvaluationResult::Number(0.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_wrap_signed() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("wrap_signed(270, 360)", &mut variables), vec![EvaluationResult::Number(-90.0)]);
+        assert_eq!(evaluate("wrap_signed(180, 360)", &mut variables), vec![EvaluationResult::Number(180.0)]);
+        assert_eq!(evaluate("wrap_signed(-180, 360)", &mut variables), vec![EvaluationResult::Number(180.0)]);
+        assert_eq!(evaluate("wrap_signed(-10, 360)", &mut variables), vec![EvaluationResult::Number(-10.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_wrap_with_zero_period() {
+        let mut variables = VarTable(vec![]);
+        assert!(matches!(evaluate("wrap(10, 0)", &mut variables)[..], [EvaluationResult::Error(_)]));
+    }
+
+    #[test]
+    fn test_evaluate_set_multiple_variables() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("set a=1, b=2; a+b", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(2.0), EvaluationResult::Number(3.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_set_updates_existing_variables() {
+        let mut variables = VarTable(vec![Variable { label: "a".to_string(), value: Value::from(5.0) }]);
+        let result = evaluate("set a = a * 2, c = a + 1; c", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(11.0), EvaluationResult::Number(11.0)]);
+        assert_eq!(variables.retrieve(&"a".to_string()).map(Value::to_f64), Some(10.0));
+    }
+
+    #[test]
+    fn test_evaluate_set_without_assignment() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("set a", &mut variables);
+        assert!(matches!(result[..], [EvaluationResult::Error(_)]));
+    }
+
+    #[test]
+    fn test_evaluation_result_display() {
+        assert_eq!(EvaluationResult::Number(8.0).to_string(), "8");
+        assert_eq!(EvaluationResult::Number(-0.5).to_string(), "-0.5");
+        let error = EvaluationError::new(ErrorKind::UndefinedVariable, "Undefined variable: x".to_string());
+        assert_eq!(EvaluationResult::Error(error).to_string(), "error: Undefined variable: x");
+        assert_eq!(EvaluationResult::Warning("'sin' shadows".to_string()).to_string(), "warning: 'sin' shadows");
+        assert_eq!(EvaluationResult::Quit.to_string(), "quit");
+    }
+
+    #[test]
+    fn test_repl_once_displays_errors() {
+        let mut state = ReplState::new();
+        let outputs = repl_once("y", &mut state);
+        assert_eq!(outputs, vec![ReplOutput::Error("error: Error occurred while evaluating 'y': Undefined variable: y (did you mean to use `let y = ...` first?)".to_string())]);
+    }
+
+    #[test]
+    fn test_evaluate_scientific_notation_enabled_by_default() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("2e3", &mut variables), vec![EvaluationResult::Number(2000.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_scientific_notation_disabled() {
+        let mut variables = VarTable(vec![Variable { label: "e3".to_string(), value: Value::from(4.0) }]);
+        let config = Config { disable_scientific_notation: true, ..Config::default() };
+        let result = evaluate_with_config("2e3", &mut variables, &config);
+        assert_eq!(result, vec![EvaluationResult::Number(2.0), EvaluationResult::Number(4.0)], "'2e3' should read as 2 followed by the variable e3");
+    }
+
+    #[test]
+    fn test_evaluate_grouped_number_input() {
+        let mut variables = VarTable(vec![]);
+        let config = Config { grouped_number_input: true, ..Config::default() };
+        let result = evaluate_with_config("1,234.56 * 2", &mut variables, &config);
+        assert_eq!(result, vec![EvaluationResult::Number(2469.12)]);
+    }
+
+    #[test]
+    fn test_evaluate_fallback_on_undefined_variable() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("undefined_var ?? 5", &mut variables), vec![EvaluationResult::Number(5.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_fallback_not_needed() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("10 ?? 5", &mut variables), vec![EvaluationResult::Number(10.0)]);
+        assert_eq!(evaluate("10 ?? undefined_var", &mut variables), vec![EvaluationResult::Number(10.0)], "The fallback should not be evaluated");
+    }
+
+    #[test]
+    fn test_evaluate_fallback_on_nan() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("0/0 ?? 7", &mut variables), vec![EvaluationResult::Number(7.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_fallback_with_nested_operands() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("(u + 1) * 2 ?? 3", &mut variables), vec![EvaluationResult::Number(3.0)]);
+        assert_eq!(evaluate("(u ?? 1) + 1", &mut variables), vec![EvaluationResult::Number(2.0)]);
+        assert_eq!(evaluate("(u + (v ?? 1)) ?? 5", &mut variables), vec![EvaluationResult::Number(5.0)]);
+        assert_eq!(evaluate("max(u ?? 1, 2 ?? u) + 1", &mut variables), vec![EvaluationResult::Number(3.0)]);
+        assert_eq!(evaluate("u ?? v ?? 4", &mut variables), vec![EvaluationResult::Number(4.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_fallback_that_fails() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("u ?? v; 1", &mut variables);
+        assert_eq!(result.len(), 2, "Result should contain the error and the next statement");
+        assert!(matches!(result[0], EvaluationResult::Error(_)));
+        assert_eq!(result[1], EvaluationResult::Number(1.0));
+    }
+
+    #[test]
+    fn test_evaluate_fallback_does_not_hide_syntax_errors() {
+        let mut variables = VarTable(vec![]);
+        assert!(matches!(evaluate("(1 + ) ?? 5", &mut variables)[..], [EvaluationResult::Error(_)]));
+        assert!(matches!(evaluate("1 ?? 2 +", &mut variables)[..], [EvaluationResult::Error(_)]));
+        assert!(matches!(evaluate("u ?? (1 +", &mut variables)[..], [EvaluationResult::Error(_)]));
+        assert_eq!(evaluate("1 ?? percent_change(0, u)", &mut variables), vec![EvaluationResult::Number(1.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_deeply_nested_undefined_variable() {
+        let mut variables = VarTable(vec![]);
+        let brackets = format!("{}u{}", "(".repeat(40), ")".repeat(40));
+        let calls = format!("{}u{}", "abs(".repeat(40), ")".repeat(40));
+
+        for input in [brackets, calls] {
+            let result = evaluate(&input, &mut variables);
+            assert!(matches!(&result[..], [EvaluationResult::Error(e)] if e.to_string().ends_with("Undefined variable: u")));
+        }
+        assert_eq!(evaluate(&format!("{}u{} ?? 1", "(".repeat(40), ")".repeat(40)), &mut variables), vec![EvaluationResult::Number(1.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_block_variable_used_outside_block() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("{ let x = 1 } + x", &mut variables);
+        assert!(matches!(&result[..], [EvaluationResult::Error(e)] if e.to_string().ends_with("Undefined variable: x")));
+    }
+
+    #[test]
+    fn test_evaluate_percent_change() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("percent_change(100, 150)", &mut variables), vec![EvaluationResult::Number(50.0)]);
+        assert_eq!(evaluate("percent_change(200, 50)", &mut variables), vec![EvaluationResult::Number(-75.0)]);
+        assert_eq!(evaluate("percent_change(-100, -50)", &mut variables), vec![EvaluationResult::Number(50.0)]);
+        assert!(matches!(evaluate("percent_change(0, 5)", &mut variables)[..], [EvaluationResult::Error(_)]));
+    }
+
+    #[test]
+    fn test_evaluate_percent_of() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("percent_of(25, 200)", &mut variables), vec![EvaluationResult::Number(12.5)]);
+        assert!(matches!(evaluate("percent_of(25, 0)", &mut variables)[..], [EvaluationResult::Error(_)]));
+    }
+
+    #[test]
+    fn test_repl_once_silent_mode_keeps_only_errors() {
+        let mut state = ReplState::new();
+        assert_eq!(repl_once("silent on", &mut state), vec![]);
+
+        let outputs = repl_once("let x = 2; x * 3; y", &mut state);
+        assert_eq!(outputs.len(), 1, "Only the error should be reported in silent mode");
+        assert!(matches!(outputs[0], ReplOutput::Error(_)));
+
+        assert_eq!(repl_once("silent off", &mut state), vec![]);
+        assert_eq!(repl_once("x", &mut state), vec![ReplOutput::Print("=2".to_string())]);
+    }
+
+    #[test]
+    fn test_repl_once_switches_notation() {
+        let mut state = ReplState::new();
+        assert_eq!(repl_once("notation eng", &mut state), vec![]);
+        assert_eq!(repl_once("12345", &mut state), vec![ReplOutput::Print("=12.345e3".to_string())]);
+
+        assert_eq!(repl_once("notation sci", &mut state), vec![]);
+        assert_eq!(repl_once("12345", &mut state), vec![ReplOutput::Print("=1.2345e4".to_string())]);
+
+        assert_eq!(repl_once("notation plain", &mut state), vec![]);
+        assert_eq!(repl_once("12345", &mut state), vec![ReplOutput::Print("=12345".to_string())]);
+    }
+
+    #[test]
+    fn test_evaluate_with_pi() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("2 * π", &mut variables), vec![EvaluationResult::Number(2.0 * std::f64::consts::PI)]);
+        assert_eq!(evaluate("pi", &mut variables), vec![EvaluationResult::Number(std::f64::consts::PI)]);
+    }
+
+    #[test]
+    fn test_evaluate_with_square_root_prefix() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("√16", &mut variables), vec![EvaluationResult::Number(4.0)]);
+        assert_eq!(evaluate("√16 + 9", &mut variables), vec![EvaluationResult::Number(13.0)], "√ should only apply to the following primary");
+        assert_eq!(evaluate("√(16 + 9)", &mut variables), vec![EvaluationResult::Number(5.0)]);
+    }
+
+    #[test]
+    fn test_repl_once_tracks_extremes() {
+        let mut state = ReplState::new();
+        assert_eq!(repl_once("extremes", &mut state), vec![ReplOutput::Print("No results yet".to_string())]);
+
+        repl_once("3; -2 * 4; 10 / 4", &mut state);
+        repl_once("0/0; 7", &mut state);
+        assert_eq!(repl_once("extremes", &mut state), vec![ReplOutput::Print("min=-8 max=7".to_string())]);
+
+        assert_eq!(repl_once("extremes reset", &mut state), vec![]);
+        repl_once("1", &mut state);
+        assert_eq!(repl_once("extremes", &mut state), vec![ReplOutput::Print("min=1 max=1".to_string())]);
+    }
+
+    #[test]
+    fn test_evaluate_power() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("2 ^ 10", &mut variables), vec![EvaluationResult::Number(1024.0)]);
+        assert_eq!(evaluate("2 ** 10", &mut variables), vec![EvaluationResult::Number(1024.0)]);
+        assert_eq!(evaluate("3 * 2 ^ 2", &mut variables), vec![EvaluationResult::Number(12.0)], "Power should bind tighter than multiplication");
+        assert_eq!(evaluate("2 ^ -1", &mut variables), vec![EvaluationResult::Number(0.5)]);
+        assert_eq!(evaluate("-2 ^ 2", &mut variables), vec![EvaluationResult::Number(-4.0)], "Negation should apply after exponentiation");
+    }
+
+    #[test]
+    fn test_evaluate_power_is_right_associative() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("2 ** 3 ** 2", &mut variables), vec![EvaluationResult::Number(512.0)]);
+        assert_eq!(evaluate("2 ^ 3 ** 2", &mut variables), vec![EvaluationResult::Number(512.0)]);
+    }
+
+    #[test]
+    fn test_repl_once_lists_variables() {
+        let mut state = ReplState::new();
+        assert_eq!(repl_once("vars", &mut state), vec![ReplOutput::Print("No variables defined".to_string())]);
+        repl_once("let x = 5; let total = -12.5", &mut state);
+        assert_eq!(repl_once("vars", &mut state), vec![ReplOutput::Print("x          5\ntotal  -12.5".to_string())]);
+    }
+
+    #[test]
+    fn test_repl_once_lists_functions() {
+        let mut state = ReplState::new();
+        let outputs = repl_once("funcs", &mut state);
+        assert!(matches!(&outputs[..], [ReplOutput::Print(table)] if table.lines().any(|line| line.starts_with("sqrt ") && line.ends_with(" 1 argument(s)"))));
+    }
+
+    #[test]
+    fn test_evaluate_map_range() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("map_range(512, 0, 1023, 0, 5)", &mut variables);
+        assert!(matches!(result[..], [EvaluationResult::Number(n)] if (n - 2.5).abs() < 0.01), "512 of 1023 should map to about 2.5");
+        assert_eq!(evaluate("map_range(5, 0, 10, 100, 0)", &mut variables), vec![EvaluationResult::Number(50.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_map_range_with_clamp() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("map_range(2000, 0, 1023, 0, 5)", &mut variables), vec![EvaluationResult::Number(2000.0 * 5.0 / 1023.0)]);
+        assert_eq!(evaluate("map_range(2000, 0, 1023, 0, 5, 1)", &mut variables), vec![EvaluationResult::Number(5.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_map_range_with_empty_input_interval() {
+        let mut variables = VarTable(vec![]);
+        assert!(matches!(evaluate("map_range(1, 3, 3, 0, 5)", &mut variables)[..], [EvaluationResult::Error(_)]));
+    }
+
+    #[test]
+    fn test_evaluate_let_shadowing_builtin_function_warns() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("let sin = 5", &mut variables);
+        assert_eq!(result.len(), 2, "Result should contain a warning and the value");
+        assert!(matches!(result[0], EvaluationResult::Warning(_)));
+        assert_eq!(result[1], EvaluationResult::Number(5.0));
+
+        assert_eq!(evaluate("sin(0)", &mut variables), vec![EvaluationResult::Number(0.0)], "Calls should still use the built-in");
+        assert_eq!(evaluate("sin + 1", &mut variables), vec![EvaluationResult::Number(6.0)], "A bare name should read the variable");
+    }
+
+    #[test]
+    fn test_evaluate_set_shadowing_builtin_constant_warns() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("set pi = 3, y = 1", &mut variables);
+        assert_eq!(result.len(), 2, "Only pi should be reported");
+        assert!(matches!(result[0], EvaluationResult::Warning(_)));
+        assert_eq!(result[1], EvaluationResult::Number(1.0));
+    }
+
+    #[test]
+    fn test_evaluate_let_without_collision_does_not_warn() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("let sine = 5", &mut variables), vec![EvaluationResult::Number(5.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_json() {
+        let mut variables = VarTable::new();
+        let json = evaluate_json("5 + 3; x", &mut variables);
+        assert_eq!(
+            json,
+            r#"[{"number":8.0},{"error":{"kind":"undefined_variable","message":"Error occurred while evaluating 'x': Undefined variable: x"}}]"#
+        );
+    }
+
+    #[test]
+    fn test_evaluate_json_with_warning_and_quit() {
+        let mut variables = VarTable::new();
+        let json = evaluate_json("let sin = 1; q", &mut variables);
+        assert!(json.starts_with(r#"[{"warning":"#), "Warnings should be serialized first");
+        assert!(json.ends_with(r#"{"number":1.0},"quit"]"#));
+    }
+
+    #[test]
+    fn test_evaluate_json_non_finite() {
+        let mut variables = VarTable::new();
+        let json = evaluate_json("0/0; 1/0; -1/0", &mut variables);
+        assert_eq!(json, r#"[{"number":"NaN"},{"number":"inf"},{"number":"-inf"}]"#);
+    }
+
+    #[test]
+    fn test_evaluate_error_kinds() {
+        let mut variables = VarTable::new();
+        let result = evaluate("1 + @; max(1", &mut variables);
+        assert!(matches!(&result[0], EvaluationResult::Error(e) if e.kind == ErrorKind::Tokenization));
+        assert!(matches!(&result[1], EvaluationResult::Error(e) if e.kind == ErrorKind::Evaluation));
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_evaluate_decimal_arithmetic_is_exact() {
+        let mut variables = VarTable::new();
+        assert_eq!(evaluate("0.1 + 0.2", &mut variables), vec![EvaluationResult::Number(0.3)]);
+        assert_eq!(evaluate("1.1 * 3 - 3.3", &mut variables), vec![EvaluationResult::Number(0.0)]);
+        assert_eq!(evaluate("19.99 * 3", &mut variables), vec![EvaluationResult::Number(59.97)]);
+        assert_eq!(evaluate("0.10 + 0.20 + 0.30 + 0.40 - 1", &mut variables), vec![EvaluationResult::Number(0.0)]);
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_evaluate_decimal_falls_back_to_f64() {
+        let mut variables = VarTable::new();
+        assert_eq!(evaluate("1e300 * 1e10", &mut variables), vec![EvaluationResult::Number(f64::INFINITY)]);
+        assert!(matches!(evaluate("nan + 0.1", &mut variables)[..], [EvaluationResult::Number(n)] if n.is_nan()));
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_evaluate_decimal_keeps_precision_between_operations() {
+        let mut variables = VarTable::new();
+        assert_eq!(evaluate("1/3*3; 2/3*3; -(1/3) * 3", &mut variables), vec![
+            EvaluationResult::Number(1.0),
+            EvaluationResult::Number(2.0),
+            EvaluationResult::Number(-1.0),
+        ]);
+        assert_eq!(evaluate("let third = 1/3; third * 3; third + third + third", &mut variables)[1..], [
+            EvaluationResult::Number(1.0),
+            EvaluationResult::Number(1.0),
+        ]);
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_evaluate_decimal_aggregates_are_exact() {
+        let mut variables = VarTable::new();
+        assert_eq!(evaluate("sum(0.1, 0.2)", &mut variables), vec![EvaluationResult::Number(0.3)]);
+        assert_eq!(evaluate("nansum(0.1, nan, 0.2)", &mut variables), vec![EvaluationResult::Number(0.3)]);
+        assert_eq!(evaluate("mean(0.1, 0.2)", &mut variables), vec![EvaluationResult::Number(0.15)]);
+        assert_eq!(evaluate("nanmean(0.1, nan, 0.2)", &mut variables), vec![EvaluationResult::Number(0.15)]);
+    }
+
+    #[test]
+    fn test_evaluate_abs_diff() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("abs_diff(3, 7)", &mut variables), vec![EvaluationResult::Number(4.0)]);
+        assert_eq!(evaluate("abs_diff(7, 3)", &mut variables), vec![EvaluationResult::Number(4.0)]);
+        assert_eq!(evaluate("abs_diff(-2, 5)", &mut variables), vec![EvaluationResult::Number(7.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_max_abs() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("max_abs(-5, 3)", &mut variables), vec![EvaluationResult::Number(5.0)]);
+        assert_eq!(evaluate("max_abs(2, -1)", &mut variables), vec![EvaluationResult::Number(2.0)]);
+        assert!(matches!(evaluate("max_abs(1)", &mut variables)[..], [EvaluationResult::Error(_)]));
+    }
+
+    #[test]
+    fn test_evaluate_with_storage_precision() {
+        let mut variables = VarTable::new();
+        let config = Config { storage_precision: Some(2), ..Config::default() };
+        let result = evaluate_with_config("let x = 1/3; x; x * 3; 1/3", &mut variables, &config);
+        assert_eq!(result, vec![
+            EvaluationResult::Number(0.33),
+            EvaluationResult::Number(0.33),
+            EvaluationResult::Number(0.99),
+            EvaluationResult::Number(1.0 / 3.0),
+        ]);
+    }
+
+    #[test]
+    fn test_evaluate_with_storage_precision_on_large_values() {
+        let mut variables = VarTable::new();
+        let config = Config { storage_precision: Some(2), ..Config::default() };
+        assert_eq!(evaluate_with_config("let x = 1e307; x", &mut variables, &config)[1], EvaluationResult::Number(1e307));
+
+        let config = Config { storage_precision: Some(400), ..Config::default() };
+        assert_eq!(evaluate_with_config("let y = 1/4; y", &mut variables, &config)[1], EvaluationResult::Number(0.25));
+    }
+
+    #[test]
+    fn test_repl_once_suggests_closest_variable() {
+        let mut state = ReplState::new();
+        repl_once("let xy = 1", &mut state);
+        let output = repl_once("x + 1", &mut state);
+        assert!(matches!(&output[..], [ReplOutput::Error(e)] if e.ends_with("(did you mean `xy`?)")), "{:?}", output);
+    }
+
+    #[test]
+    fn test_repl_once_suggests_let_for_unknown_variable() {
+        let mut state = ReplState::new();
+        let output = repl_once("total * 2", &mut state);
+        assert!(matches!(&output[..], [ReplOutput::Error(e)] if e.ends_with("(did you mean to use `let total = ...` first?)")));
+    }
+
+    #[test]
+    fn test_repl_once_suggests_closing_parenthesis() {
+        let mut state = ReplState::new();
+        for input in ["(1 + 2", "max(1, 2"] {
+            let output = repl_once(input, &mut state);
+            assert!(matches!(&output[..], [ReplOutput::Error(e)] if e.ends_with("(add a closing `)`)")), "{:?}", output);
+        }
+    }
+
+    #[test]
+    fn test_repl_once_suggests_set_on_redefinition() {
+        let mut state = ReplState::new();
+        let output = repl_once("let a = 1; let a = 2", &mut state);
+        assert!(matches!(&output[1], ReplOutput::Error(e) if e.ends_with("(use `set a = ...` to define or update it)")));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("x", "xy"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("π", "pi"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_repl_once_displays_special_values_as_words() {
+        let mut state = ReplState::new();
+        let outputs = repl_once("0/0; 1/0; -1/0", &mut state);
+        assert_eq!(outputs, vec![
+            ReplOutput::Print("=not a number".to_string()),
+            ReplOutput::Print("=infinity".to_string()),
+            ReplOutput::Print("=-infinity".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_evaluate_square_and_curly_brackets() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("[2+3]*4", &mut variables), vec![EvaluationResult::Number(20.0)]);
+        assert_eq!(evaluate("{2+3}*4", &mut variables), vec![EvaluationResult::Number(20.0)]);
+        assert_eq!(evaluate("{[2 + (1 + 2)] * 2} / 5", &mut variables), vec![EvaluationResult::Number(2.0)]);
+        assert_eq!(evaluate("max([1 + 2], {3})", &mut variables), vec![EvaluationResult::Number(3.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_mismatched_brackets() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("[2+3)", &mut variables);
+        assert!(matches!(&result[..], [EvaluationResult::Error(e)] if e.message.ends_with("Expected ']' to close '[' but got ')'")), "{:?}", result);
+        let result = evaluate("{2+3", &mut variables);
+        assert!(matches!(&result[..], [EvaluationResult::Error(e)] if e.message.ends_with("Expected closing '}'")), "{:?}", result);
+    }
+
+    #[test]
+    fn test_eval_prefix_uses_longest_complete_prefix() {
+        let variables = VarTable::new();
+        assert_eq!(eval_prefix("2 + 3 *", &variables), Some(5.0));
+        assert_eq!(eval_prefix("2 + 3 * 4", &variables), Some(14.0));
+        assert_eq!(eval_prefix("max(1, 2) +", &variables), Some(2.0));
+        assert_eq!(eval_prefix("1 + 1; 5", &variables), Some(2.0));
+        assert_eq!(eval_prefix("{ let t = 2; t * 3 } +", &variables), Some(6.0));
+    }
+
+    #[test]
+    fn test_eval_prefix_without_complete_prefix() {
+        let variables = VarTable::new();
+        assert_eq!(eval_prefix("(2 + 3", &variables), None);
+        assert_eq!(eval_prefix("", &variables), None);
+        assert_eq!(eval_prefix("let x = 5", &variables), None);
+    }
+
+    #[test]
+    fn test_eval_prefix_does_not_modify_variables() {
+        let variables = VarTable(vec![Variable { label: "x".to_string(), value: Value::from(2.0) }]);
+        assert_eq!(eval_prefix("x * 10 -", &variables), Some(20.0));
+        assert_eq!(eval_prefix("x = 5", &variables), Some(2.0));
+        assert_eq!(variables.retrieve(&"x".to_string()).map(Value::to_f64), Some(2.0));
+    }
+
+    #[test]
+    fn test_evaluate_floor_ceil_round() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("floor(7/2); ceil(7/2); round(7/2)", &mut variables), vec![
+            EvaluationResult::Number(3.0),
+            EvaluationResult::Number(4.0),
+            EvaluationResult::Number(4.0),
+        ]);
+        assert_eq!(evaluate("floor(-3.5); ceil(-3.5); round(-2.5)", &mut variables), vec![
+            EvaluationResult::Number(-4.0),
+            EvaluationResult::Number(-3.0),
+            EvaluationResult::Number(-3.0),
+        ]);
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_evaluate_decimal_rounding_is_exact() {
+        let mut variables = VarTable::new();
+        assert_eq!(evaluate("floor(7/2)", &mut variables), vec![EvaluationResult::Number(3.0)]);
+        // Binary floating point makes (0.1 + 0.2) * 10 slightly more than 3
+        assert_eq!(evaluate("ceil((0.1 + 0.2) * 10)", &mut variables), vec![EvaluationResult::Number(3.0)]);
+        assert_eq!(evaluate("round(1.005 * 1000)", &mut variables), vec![EvaluationResult::Number(1005.0)]);
+    }
+
+    #[test]
+    fn test_classify_args() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(classify_args(&args(&[])), Invocation::Repl);
+        assert_eq!(classify_args(&args(&["--help"])), Invocation::Help);
+        assert_eq!(classify_args(&args(&["-h"])), Invocation::Help);
+        assert_eq!(classify_args(&args(&["--version"])), Invocation::Version);
+        assert_eq!(classify_args(&args(&["1 + 1"])), Invocation::Evaluate("1 + 1".to_string()));
+        assert_eq!(classify_args(&args(&["-5", "+", "1"])), Invocation::Evaluate("-5 + 1".to_string()));
+        assert_eq!(classify_args(&args(&["--verbose"])), Invocation::UnknownFlag("--verbose".to_string()));
+    }
+
+    #[test]
+    fn test_evaluate_block_yields_last_value() {
+        let mut variables = VarTable(vec![
+            Variable { label: "a".to_string(), value: Value::from(2.0) },
+            Variable { label: "b".to_string(), value: Value::from(3.0) },
+        ]);
+        assert_eq!(evaluate("{ let t = a + b; t * t }", &mut variables), vec![EvaluationResult::Number(25.0)]);
+        assert_eq!(evaluate("{ let t = 2; t + 1; } * 2", &mut variables), vec![EvaluationResult::Number(6.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_block_variables_do_not_leak() {
+        let mut variables = VarTable(vec![Variable { label: "a".to_string(), value: Value::from(2.0) }]);
+        let result = evaluate("{ let t = 5; a = t }; a; t", &mut variables);
+        assert_eq!(result[..2], [EvaluationResult::Number(5.0), EvaluationResult::Number(5.0)]);
+        assert!(matches!(&result[2], EvaluationResult::Error(e) if e.kind == ErrorKind::UndefinedVariable));
+        assert_eq!(evaluate("let t = 1; t", &mut variables), vec![EvaluationResult::Number(1.0), EvaluationResult::Number(1.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_block_scope_ends_on_error() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("{ let t = 1; t + x }", &mut variables);
+        assert!(matches!(&result[..], [EvaluationResult::Error(_)]));
+        assert!(!variables.contains(&"t".to_string()));
+    }
+
+    #[test]
+    fn test_evaluate_copysign() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("copysign(3, -1)", &mut variables), vec![EvaluationResult::Number(-3.0)]);
+        assert_eq!(evaluate("copysign(-3, 2)", &mut variables), vec![EvaluationResult::Number(3.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_nextafter() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("nextafter(1, 2)", &mut variables), vec![EvaluationResult::Number(1.0 + f64::EPSILON)]);
+        assert_eq!(evaluate("nextafter(1, 0)", &mut variables), vec![EvaluationResult::Number(1.0 - f64::EPSILON / 2.0)]);
+        assert_eq!(evaluate("nextafter(1, 1)", &mut variables), vec![EvaluationResult::Number(1.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_adjacent_operands_are_separate_statements() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("5 3", &mut variables), vec![EvaluationResult::Number(5.0), EvaluationResult::Number(3.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_adjacent_operands_in_strict_mode() {
+        let mut variables = VarTable(vec![]);
+        let config = Config { require_operators: true, ..Config::default() };
+        let result = evaluate_with_config("5 3; 2 (1); 4", &mut variables, &config);
+        assert_eq!(result, vec![
+            EvaluationResult::Error(EvaluationError::new(ErrorKind::Evaluation, "Error occurred while evaluating '5': Missing operator between operands".to_string())),
+            EvaluationResult::Error(EvaluationError::new(ErrorKind::Evaluation, "Error occurred while evaluating '2': Missing operator between operands".to_string())),
+            EvaluationResult::Number(4.0),
+        ]);
+    }
+
+    #[test]
+    fn test_evaluate_labeled_result() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("total: 2 + 3; @total * 2", &mut variables);
+        assert_eq!(result, vec![EvaluationResult::Number(5.0), EvaluationResult::Number(10.0)]);
+        assert_eq!(evaluate("total: @total + 1; total", &mut variables), vec![EvaluationResult::Number(6.0), EvaluationResult::Number(6.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_undefined_reference() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("@pi", &mut variables);
+        assert!(matches!(&result[..], [EvaluationResult::Error(e)] if e.kind == ErrorKind::UndefinedVariable));
+    }
+
+    #[test]
+    fn test_repl_once_runs_macro() {
+        let mut state = ReplState::new();
+        repl_once("let x = 1", &mut state);
+        assert_eq!(repl_once("macro inc = x = x + 1", &mut state), vec![]);
+        assert_eq!(repl_once("run inc", &mut state), vec![ReplOutput::Print("=2".to_string())]);
+        assert_eq!(repl_once("run inc", &mut state), vec![ReplOutput::Print("=3".to_string())]);
+        assert_eq!(state.variables.retrieve(&"x".to_string()).map(Value::to_f64), Some(3.0));
+    }
+
+    #[test]
+    fn test_repl_once_macro_errors() {
+        let mut state = ReplState::new();
+        assert_eq!(repl_once("run missing", &mut state), vec![ReplOutput::Error("error: Macro missing is not defined".to_string())]);
+        assert!(matches!(&repl_once("macro = 1", &mut state)[..], [ReplOutput::Error(_)]));
+    }
+
+    #[test]
+    fn test_repl_once_stops_recursive_macros() {
+        let mut state = ReplState::new();
+        repl_once("macro a = run b", &mut state);
+        repl_once("macro b = run a", &mut state);
+        assert_eq!(repl_once("run a", &mut state), vec![ReplOutput::Error("error: Macro a runs itself".to_string())]);
+    }
+
+    #[test]
+    fn test_evaluate_stores_nan_by_default() {
+        let mut variables = VarTable(vec![]);
+        let result = evaluate("let x = 0/0", &mut variables);
+        assert!(matches!(result[..], [EvaluationResult::Number(n)] if n.is_nan()));
+    }
+
+    #[test]
+    fn test_evaluate_rejects_non_finite_values_when_guarded() {
+        let mut variables = VarTable(vec![]);
+        let config = Config { reject_non_finite: true, ..Config::default() };
+        let result = evaluate_with_config("let x = 0/0; set y = 1/0; let z = (0/0) ?? 1; z", &mut variables, &config);
+        assert!(matches!(&result[0], EvaluationResult::Error(e) if e.message.ends_with("Refusing to store NaN in x")));
+        assert!(matches!(&result[1], EvaluationResult::Error(e) if e.message.ends_with("Refusing to store inf in y")));
+        assert_eq!(result[2..], [EvaluationResult::Number(1.0), EvaluationResult::Number(1.0)]);
+        assert!(!variables.contains(&"x".to_string()));
+    }
+
+    #[test]
+    fn test_evaluate_script() {
+        let (results, variables) = evaluate_script("2+2\n3+3\nlet x=1");
+        assert_eq!(results, vec![EvaluationResult::Number(4.0), EvaluationResult::Number(6.0), EvaluationResult::Number(1.0)]);
+        assert_eq!(variables.retrieve(&"x".to_string()).map(Value::to_f64), Some(1.0));
+    }
+
+    #[test]
+    fn test_evaluate_script_with_blank_lines_and_blocks() {
+        let (results, _) = evaluate_script("let a = 2; a\n\n{\n  let t = a + 1\n  t * t\n}\n");
+        assert_eq!(results, vec![EvaluationResult::Number(2.0), EvaluationResult::Number(2.0), EvaluationResult::Number(9.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_sinc() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("sinc(0)", &mut variables), vec![EvaluationResult::Number(1.0)]);
+        assert!(matches!(evaluate("sinc(pi)", &mut variables)[..], [EvaluationResult::Number(n)] if n.abs() < 1e-15));
+    }
+
+    #[test]
+    fn test_evaluate_logistic() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("logistic(0)", &mut variables), vec![EvaluationResult::Number(0.5)]);
+        assert_eq!(evaluate("logistic(-1000); logistic(1000)", &mut variables), vec![EvaluationResult::Number(0.0), EvaluationResult::Number(1.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_relu() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("relu(-2); relu(0); relu(3)", &mut variables), vec![
+            EvaluationResult::Number(0.0),
+            EvaluationResult::Number(0.0),
+            EvaluationResult::Number(3.0),
+        ]);
+    }
+
+    #[test]
+    fn test_evaluate_sum_and_mean() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("sum(1, 2, 3); mean(1, 2, 3)", &mut variables), vec![EvaluationResult::Number(6.0), EvaluationResult::Number(2.0)]);
+        assert!(matches!(evaluate("sum(1, nan, 3)", &mut variables)[..], [EvaluationResult::Number(n)] if n.is_nan()));
+        assert!(matches!(evaluate("mean(1, nan, 3)", &mut variables)[..], [EvaluationResult::Number(n)] if n.is_nan()));
+    }
+
+    #[test]
+    fn test_evaluate_nansum_and_nanmean() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("nansum(1, nan, 3); nanmean(1, nan, 3)", &mut variables), vec![EvaluationResult::Number(4.0), EvaluationResult::Number(2.0)]);
+        assert_eq!(evaluate("nansum(nan)", &mut variables), vec![EvaluationResult::Number(0.0)]);
+        assert!(matches!(evaluate("nanmean(nan, nan)", &mut variables)[..], [EvaluationResult::Number(n)] if n.is_nan()));
+    }
+
+    #[test]
+    fn test_timing_stats() {
+        let mut stats = TimingStats::new();
+        assert_eq!(stats.mean(), None);
+
+        for micros in [3, 1, 5] {
+            stats.record(Duration::from_micros(micros));
+        }
+        assert_eq!(stats.min, Duration::from_micros(1));
+        assert_eq!(stats.mean(), Some(Duration::from_micros(3)));
+        assert_eq!(stats.max, Duration::from_micros(5));
+    }
+
+    #[test]
+    fn test_repl_once_bench() {
+        let mut state = ReplState::new();
+        repl_once("let x = 1", &mut state);
+        let outputs = repl_once("bench 10 x = x + 1", &mut state);
+        assert_eq!(outputs[0], ReplOutput::Print("=2".to_string()));
+        assert!(matches!(&outputs[1], ReplOutput::Print(stats) if stats.starts_with("10 runs: min ")));
+        assert_eq!(state.variables.retrieve(&"x".to_string()).map(Value::to_f64), Some(1.0));
+        assert!(matches!(&repl_once("bench many 1", &mut state)[..], [ReplOutput::Error(_)]));
+    }
+
+    #[test]
+    fn test_repl_once_bench_does_not_quit() {
+        let mut state = ReplState::new();
+        let outputs = repl_once("bench 2 1; q", &mut state);
+        assert_eq!(outputs.len(), 2, "Only the result and the timing line should be reported");
+        assert!(!outputs.contains(&ReplOutput::Quit));
+    }
+
+    #[test]
+    fn test_evaluate_root_prefixes() {
+        let mut variables = VarTable(vec![]);
+        assert_eq!(evaluate("√16; ∛27; ∜16", &mut variables), vec![
+            EvaluationResult::Number(4.0),
+            EvaluationResult::Number(3.0),
+            EvaluationResult::Number(2.0),
+        ]);
+        assert_eq!(evaluate("√(√16); ∛-8; 2 * ∛∛512", &mut variables), vec![
+            EvaluationResult::Number(2.0),
+            EvaluationResult::Number(-2.0),
+            EvaluationResult::Number(4.0),
+        ]);
+    }
+
+    #[test]
+    fn test_repl_once_with_german_locale() {
+        let config = Config { locale: Locale::German, grouped_number_input: true, thousands_separator: true, ..Config::default() };
+        let mut state = ReplState::with_config(config);
+        assert_eq!(repl_once("1.234,56 * 2; max(1,5, 2)", &mut state), vec![
+            ReplOutput::Print("=2.469,12".to_string()),
+            ReplOutput::Print("=2".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_evaluate_to_words() {
+        let mut variables = VarTable::new();
+        assert_eq!(evaluate("to_words(123)", &mut variables), vec![EvaluationResult::Text("one hundred twenty-three".to_string())]);
+        assert_eq!(evaluate("to_words(2 * 500); 1", &mut variables), vec![
+            EvaluationResult::Text("one thousand".to_string()),
+            EvaluationResult::Number(1.0),
+        ]);
+    }
+
+    #[test]
+    fn test_evaluate_to_words_in_an_expression() {
+        let mut variables = VarTable::new();
+        assert!(matches!(evaluate("to_words(2) + 1", &mut variables)[..], [EvaluationResult::Error(_)]));
+        assert!(matches!(evaluate("1 + to_words(2)", &mut variables)[..], [EvaluationResult::Error(_)]));
+    }
+
+    #[test]
+    fn test_repl_once_prints_text() {
+        let mut state = ReplState::new();
+        assert_eq!(repl_once("to_words(-7)", &mut state), vec![ReplOutput::Print("=minus seven".to_string())]);
+    }
+
+    #[test]
+    fn test_evaluate_roman_numerals() {
+        let mut variables = VarTable::new();
+        assert_eq!(evaluate("to_roman(2024)", &mut variables), vec![EvaluationResult::Text("MMXXIV".to_string())]);
+        assert_eq!(evaluate("from_roman(\"MMXXIV\") + 1", &mut variables), vec![EvaluationResult::Number(2025.0)]);
+    }
+
+    #[test]
+    fn test_evaluate_roman_numerals_invalid() {
+        let mut variables = VarTable::new();
+        assert!(matches!(evaluate("to_roman(4000)", &mut variables)[..], [EvaluationResult::Error(_)]));
+        assert!(matches!(evaluate("from_roman(\"IIII\")", &mut variables)[..], [EvaluationResult::Error(_)]));
+        assert!(matches!(evaluate("from_roman(14)", &mut variables)[..], [EvaluationResult::Error(_)]));
+    }
+
+    #[test]
+    fn test_evaluate_block_let_hides_outer_variable() {
+        let mut variables = VarTable::new();
+        let result = evaluate("let t = 1; { let t = 2; t = t * 5; t } + t; t", &mut variables);
+        assert_eq!(result, vec![
+            EvaluationResult::Number(1.0),
+            EvaluationResult::Number(11.0),
+            EvaluationResult::Number(1.0),
+        ]);
+        assert_eq!(variables.retrieve(&"t".to_string()).map(Value::to_f64), Some(1.0));
+        assert!(matches!(evaluate("{ let t = 3; let t = 4 }", &mut variables)[..], [EvaluationResult::Error(_)]));
+    }
 }
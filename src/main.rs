@@ -1,8 +1,42 @@
-use ppp_calculator::calculate;
+use std::process::exit;
 
+use ppp_calculator::{calculate, classify_args, repl_once, Invocation, ReplOutput, ReplState};
+
+const USAGE: &str = "Usage: ppp_calculator [EXPRESSION]
+
+Evaluates EXPRESSION and prints the results, or starts an interactive session when no expression is given.
+
+Options:
+    -h, --help       Print this help and exit
+    -V, --version    Print the version and exit";
 
 fn main() {
-    println!("Enter an expression or q to exit.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match classify_args(&args) {
+        Invocation::Help => println!("{}", USAGE),
+        Invocation::Version => println!("ppp_calculator {}", env!("CARGO_PKG_VERSION")),
+        Invocation::UnknownFlag(flag) => {
+            eprintln!("Unknown option '{}'\n\n{}", flag, USAGE);
+            exit(2)
+        }
+        Invocation::Evaluate(expression) => {
+            let mut failed = false;
+            for output in repl_once(&expression, &mut ReplState::new()) {
+                match output {
+                    ReplOutput::Print(text) => println!("{}", text),
+                    ReplOutput::Error(e) => {
+                        eprintln!("{}", e);
+                        failed = true;
+                    }
+                    ReplOutput::Warning(w) => eprintln!("{}", w),
+                    ReplOutput::Quit => break
+                }
+            }
+            if failed { exit(1) }
+        }
+        Invocation::Repl => {
+            println!("Enter an expression or q to exit.
 Examples:
     1 + 1
     2 * 3
@@ -10,5 +44,7 @@ Examples:
     let x = 1
     let y = 2; y + 4");
 
-    calculate();
+            calculate();
+        }
+    }
 }
@@ -3,17 +3,36 @@ use std::fmt::Display;
 use anyhow::Result;
 use thiserror::Error;
 
+use crate::Config;
+
 const DECLARE: &str = "let";
+const SET: &str = "set";
 const END_STATEMENT: char = ';';
 const QUIT: char = 'q';
-
-static SYMBOLS: [char; 10] = [
+const LOGICAL_XOR: &str = "^^";
+const PIPE: &str = "|>";
+const FALLBACK: &str = "??";
+const POWER: char = '^';
+const POWER_ALIAS: &str = "**";
+const LABEL: char = ':';
+const REFERENCE: char = '@';
+const QUOTE: char = '"';
+
+static SYMBOLS: [char; 21] = [
     /* --- Operators --- */
-    '+', '-', '*', '/', '!',
+    '+', '-', '*', '/', '!', '%', POWER,
+    '√', // Square root prefix
+    '∛', // Cube root prefix
+    '∜', // Fourth root prefix
     /* --- Parentheses --- */
     '(', ')',
+    '[', ']', // Alternative grouping
+    '{', '}', // Alternative grouping
+    /* --- Separators --- */
+    ',', // Function arguments
     /* --- Commands --- */
     '=', // Assign
+    LABEL, // Label a result, `total: 2 + 3`
     END_STATEMENT, // End statement
     QUIT, // Quit
 ];
@@ -23,9 +42,16 @@ pub enum Token {
     Number(f64),
     Symbol(char),
     Let,
+    Set,
     Name(String),
     EndStatement,
     Quit,
+    LogicalXor,
+    Pipe,
+    Fallback,
+    Reference(String),
+    // A quoted argument such as the numeral in `from_roman("XIV")`
+    Text(String),
 }
 
 impl Display for Token {
@@ -34,9 +60,15 @@ impl Display for Token {
             Token::Number(number) => write!(f, "{}", number),
             Token::Symbol(symbol) => write!(f, "{}", symbol),
             Token::Let => write!(f, "{}", DECLARE),
+            Token::Set => write!(f, "{}", SET),
             Token::Name(name) => write!(f, "{}", name),
             Token::EndStatement => write!(f, "{}", END_STATEMENT),
             Token::Quit => write!(f, "{}", QUIT),
+            Token::LogicalXor => write!(f, "{}", LOGICAL_XOR),
+            Token::Pipe => write!(f, "{}", PIPE),
+            Token::Fallback => write!(f, "{}", FALLBACK),
+            Token::Reference(label) => write!(f, "{}{}", REFERENCE, label),
+            Token::Text(text) => write!(f, "{0}{1}{0}", QUOTE, text),
         }
     }
 }
@@ -47,28 +79,117 @@ pub enum TokenizationError {
     InvalidSymbol(char),
     #[error("Invalid number: {0}")]
     InvalidNumber(String),
+    #[error("Redundant leading zero in number: {0}")]
+    LeadingZero(String),
+    #[error("Missing closing quote after: {0}")]
+    UnclosedText(String),
+}
+
+// A saved position in a TokenStream, including any tokens that were put back at the time
+pub struct Mark {
+    pos: usize,
+    put_back: Vec<Token>,
+    nesting: isize,
 }
 
 pub struct TokenStream {
     buffer: Vec<char>,
     pos: usize,
     put_back: Vec<Token>,
+    nesting: isize,
+    // Set while an operand is parsed only to check its syntax, see `checking`
+    checking: bool,
+    strict_leading_zeros: bool,
+    scientific_notation: bool,
+    grouped_number_input: bool,
+    decimal_separator: char,
+    group_separator: char,
 }
 
 impl TokenStream {
+    // Raw input that may not be valid UTF-8, invalid sequences are replaced before tokenizing. Every caller outside
+    // the tests already has a &str and uses from_chars.
+    #[cfg(test)]
     pub fn new(input: &[u8]) -> TokenStream {
+        TokenStream::from_chars(&String::from_utf8_lossy(input))
+    }
+
+    pub fn from_chars(input: &str) -> TokenStream {
         TokenStream {
-            buffer: String::from_utf8_lossy(input).chars().collect(),
+            buffer: input.chars().collect(),
             pos: 0,
             put_back: Vec::new(),
+            nesting: 0,
+            checking: false,
+            strict_leading_zeros: false,
+            scientific_notation: true,
+            grouped_number_input: false,
+            decimal_separator: '.',
+            group_separator: ',',
+        }
+    }
+
+    pub fn with_config(input: &str, config: &Config) -> TokenStream {
+        TokenStream {
+            strict_leading_zeros: config.strict_leading_zeros,
+            scientific_notation: !config.disable_scientific_notation,
+            grouped_number_input: config.grouped_number_input,
+            decimal_separator: config.locale.decimal_separator(),
+            group_separator: config.locale.group_separator(),
+            ..TokenStream::from_chars(input)
         }
     }
 
     pub fn next(&mut self) -> Result<Option<Token>> {
-        if let Some(token) = self.put_back.pop() {
-            return Ok(Some(token));
+        let token = match self.put_back.pop() {
+            Some(token) => Some(token),
+            None => self.read_token()?,
+        };
+
+        match token {
+            Some(Token::Symbol(c)) if closing_bracket(c).is_some() => self.nesting += 1,
+            Some(Token::Symbol(c)) if is_closing_bracket(c) => self.nesting -= 1,
+            _ => {}
         }
 
+        Ok(token)
+    }
+
+    pub fn mark(&self) -> Mark {
+        Mark {
+            pos: self.pos,
+            put_back: self.put_back.clone(),
+            nesting: self.nesting,
+        }
+    }
+
+    pub fn reset_to(&mut self, mark: Mark) {
+        self.pos = mark.pos;
+        self.put_back = mark.put_back;
+        self.nesting = mark.nesting;
+    }
+
+    // Index of the character after the last token read from the input, tokens that were put back are not accounted for
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    // Whether the tokens are being parsed to check their syntax rather than to evaluate them
+    pub fn checking(&self) -> bool {
+        self.checking
+    }
+
+    // Returns the previous setting so nested checks can restore it
+    pub fn set_checking(&mut self, checking: bool) -> bool {
+        std::mem::replace(&mut self.checking, checking)
+    }
+
+    // How many parentheses the tokens handed out so far have left open
+    pub fn nesting(&self) -> isize {
+        self.nesting
+    }
+
+    fn read_token(&mut self) -> Result<Option<Token>> {
         // Skip whitespaces
         while self.pos < self.buffer.len() && self.buffer[self.pos].is_whitespace() {
             self.pos += 1;
@@ -78,18 +199,39 @@ impl TokenStream {
             return Ok(None);
         }
 
+        if self.next_chars_are(LOGICAL_XOR) {
+            self.pos += LOGICAL_XOR.len();
+            return Ok(Some(Token::LogicalXor));
+        }
+
+        if self.next_chars_are(PIPE) {
+            self.pos += PIPE.len();
+            return Ok(Some(Token::Pipe));
+        }
+
+        if self.next_chars_are(FALLBACK) {
+            self.pos += FALLBACK.len();
+            return Ok(Some(Token::Fallback));
+        }
+
+        // Python style power operator, the parser only needs to know about one of them
+        if self.next_chars_are(POWER_ALIAS) {
+            self.pos += POWER_ALIAS.len();
+            return Ok(Some(Token::Symbol(POWER)));
+        }
+
         let c = self.read_char();
-        if is_beginning_of_literal(c) {
+        if is_beginning_of_literal(c, self.decimal_separator) {
             self.pos -= 1;
             let number = self.read_number()?;
             Ok(Some(Token::Number(number)))
         } else if is_valid_symbol(c) {
             match c {
-                END_STATEMENT => if self.pos < self.buffer.len() - 1 { Ok(Some(Token::EndStatement)) } else { Ok(None) },
+                END_STATEMENT => if self.pos < self.buffer.len() { Ok(Some(Token::EndStatement)) } else { Ok(None) },
                 QUIT => Ok(Some(Token::Quit)),
                 _ => Ok(Some(Token::Symbol(c)))
             }
-        } else if c.is_alphabetic() {
+        } else if is_beginning_of_name(c) {
             self.pos -= 1;
             let string = self.read_string();
 
@@ -97,7 +239,21 @@ impl TokenStream {
                 return Ok(Some(Token::Let));
             }
 
+            if string == SET {
+                return Ok(Some(Token::Set));
+            }
+
             Ok(Some(Token::Name(string)))
+        } else if c == REFERENCE && self.buffer.get(self.pos).is_some_and(|c| is_beginning_of_name(*c)) {
+            Ok(Some(Token::Reference(self.read_string())))
+        } else if c == QUOTE {
+            let text: String = self.buffer[self.pos..].iter().take_while(|c| **c != QUOTE).collect();
+            self.pos += text.chars().count();
+            if self.pos >= self.buffer.len() {
+                return Err(TokenizationError::UnclosedText(text).into());
+            }
+            self.pos += 1;
+            Ok(Some(Token::Text(text)))
         } else {
             Err(TokenizationError::InvalidSymbol(c).into())
         }
@@ -112,13 +268,20 @@ impl TokenStream {
     }
 
     pub fn put_back(&mut self, token: Token) {
+        match token {
+            Token::Symbol(c) if closing_bracket(c).is_some() => self.nesting -= 1,
+            Token::Symbol(c) if is_closing_bracket(c) => self.nesting += 1,
+            _ => {}
+        }
+
         self.put_back.push(token);
     }
 
-    // The current expression is deemed invalid, discard everything until the next semicolon, or the end of the input
+    // The current statement is deemed invalid, discard everything until the next semicolon or the end of the input.
+    // Semicolons inside an unfinished block belong to it, so they are discarded too.
     pub fn discard_invalid(&mut self) {
         while let Some(token) = self.next().unwrap_or(None) {
-            if token == Token::EndStatement {
+            if token == Token::EndStatement && self.nesting <= 0 {
                 break;
             }
         }
@@ -128,16 +291,42 @@ impl TokenStream {
         let mut number = String::new();
         while self.pos < self.buffer.len() {
             let c = self.buffer[self.pos];
-            if is_part_of_literal(c, &number) {
+            if self.grouped_number_input && c == self.group_separator && self.is_group_separator(&number) {
+                self.pos += 1;
+            } else if c == self.decimal_separator && c != '.' {
+                // Only a comma followed by a digit is a decimal comma, `max(1, 2)` still has two arguments
+                if !self.buffer.get(self.pos + 1).is_some_and(|c| c.is_ascii_digit()) {
+                    break;
+                }
+                number.push('.');
+                self.pos += 1;
+            } else if c == '.' && self.decimal_separator != '.' {
+                break;
+            } else if is_part_of_literal(c, &number, self.scientific_notation) {
                 number.push(c);
                 self.pos += 1;
             } else {
                 break;
             }
         }
+
+        if self.strict_leading_zeros && has_leading_zero(&number) {
+            return Err(TokenizationError::LeadingZero(number).into());
+        }
+
         number.parse().map_err(|_| TokenizationError::InvalidNumber(number).into())
     }
 
+    // A group separator sits between digits of the integer part
+    fn is_group_separator(&self, number: &str) -> bool {
+        let next_is_digit = self.buffer.get(self.pos + 1).is_some_and(|c| c.is_ascii_digit());
+        next_is_digit && number.ends_with(|c: char| c.is_ascii_digit()) && !number.contains(['.', 'e', 'E'])
+    }
+
+    fn next_chars_are(&self, expected: &str) -> bool {
+        expected.chars().enumerate().all(|(i, c)| self.buffer.get(self.pos + i) == Some(&c))
+    }
+
     fn read_char(&mut self) -> char {
         let c = self.buffer[self.pos];
         self.pos += 1;
@@ -148,7 +337,7 @@ impl TokenStream {
         let mut name = String::new();
         while self.pos < self.buffer.len() {
             let c = self.buffer[self.pos];
-            if c.is_alphanumeric() {
+            if is_part_of_name(c) {
                 name.push(c);
                 self.pos += 1;
             } else { break; }
@@ -157,6 +346,80 @@ impl TokenStream {
     }
 }
 
+// Each opening bracket with the one that closes it. `[2 + 3]` and `{2 + 3}` group just like parentheses.
+static BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+pub fn closing_bracket(open: char) -> Option<char> {
+    BRACKETS.iter().find(|(opening, _)| *opening == open).map(|(_, closing)| *closing)
+}
+
+pub fn is_closing_bracket(c: char) -> bool {
+    BRACKETS.iter().any(|(_, closing)| *closing == c)
+}
+
+// Parses a single numeric literal exactly as the tokenizer would. Signs are operators, not part of literals.
+pub fn parse_number(s: &str) -> Result<f64> {
+    let mut ts = TokenStream::from_chars(s.trim());
+
+    if ts.buffer.first().is_none_or(|c| !is_beginning_of_literal(*c, ts.decimal_separator)) {
+        return Err(TokenizationError::InvalidNumber(s.to_string()).into());
+    }
+
+    let number = ts.read_number()?;
+
+    if ts.pos < ts.buffer.len() {
+        return Err(TokenizationError::InvalidNumber(s.to_string()).into());
+    }
+
+    Ok(number)
+}
+
+// Keeps the tokens of a text that is being edited, so an edit only re-tokenizes from the first token it can affect.
+// Offsets count characters, not bytes. Tokenizing stops at the first invalid token.
+pub struct ReparseState {
+    stream: TokenStream,
+    // Each token with the offset just past its last character
+    tokens: Vec<(Token, usize)>,
+    reused: usize,
+}
+
+impl ReparseState {
+    pub fn new(input: &str) -> ReparseState {
+        let mut state = ReparseState { stream: TokenStream::from_chars(input), tokens: vec![], reused: 0 };
+        state.tokenize_from(0);
+        state
+    }
+
+    // Replaces `old_len` characters at `offset` with `new_text`. A token ending right at the offset is re-read too,
+    // since the edit may extend it, like typing a digit after a number.
+    pub fn edit(&mut self, offset: usize, old_len: usize, new_text: &str) {
+        let buffer = &mut self.stream.buffer;
+        let offset = offset.min(buffer.len());
+        let end = (offset + old_len).min(buffer.len());
+        buffer.splice(offset..end, new_text.chars());
+
+        self.reused = self.tokens.iter().take_while(|(_, token_end)| *token_end < offset).count();
+        self.tokens.truncate(self.reused);
+        self.tokenize_from(self.tokens.last().map_or(0, |(_, token_end)| *token_end));
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = &Token> {
+        self.tokens.iter().map(|(token, _)| token)
+    }
+
+    // How many tokens the last edit kept without re-tokenizing them
+    pub fn reused(&self) -> usize {
+        self.reused
+    }
+
+    fn tokenize_from(&mut self, pos: usize) {
+        self.stream.pos = pos;
+        while let Ok(Some(token)) = self.stream.read_token() {
+            self.tokens.push((token, self.stream.pos));
+        }
+    }
+}
+
 impl Iterator for TokenStream {
     type Item = Result<Option<Token>>;
 
@@ -165,19 +428,36 @@ impl Iterator for TokenStream {
     }
 }
 
-fn is_beginning_of_literal(c: char) -> bool {
-    c.is_digit(10) || c == '.'
+// A decimal comma can't start a number, since it also separates function arguments
+fn is_beginning_of_literal(c: char, decimal_separator: char) -> bool {
+    c.is_ascii_digit() || (c == '.' && decimal_separator == '.')
 }
 
-fn is_part_of_literal(c: char, ctx: &str) -> bool {
+fn is_part_of_literal(c: char, ctx: &str, scientific_notation: bool) -> bool {
+    if !scientific_notation {
+        return c.is_ascii_digit() || c == '.';
+    }
+
     // account for scientific notation
     if ctx.ends_with('e') || ctx.ends_with('E') {
-        return c.is_digit(10) || c == '-' || c == '+';
+        return c.is_ascii_digit() || c == '-' || c == '+';
     }
 
-    c.is_digit(10) || c == '.' || c == 'e' || c == 'E'
+    c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E'
 }
 
+fn is_beginning_of_name(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_part_of_name(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn has_leading_zero(number: &str) -> bool {
+    let integer_part = number.split(['.', 'e', 'E']).next().unwrap_or_default();
+    integer_part.len() > 1 && integer_part.starts_with('0')
+}
 
 fn is_valid_symbol(c: char) -> bool {
     SYMBOLS.contains(&c)
@@ -186,6 +466,7 @@ fn is_valid_symbol(c: char) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Locale;
 
     struct TestCase {
         input: &'static str,
@@ -381,6 +662,177 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn test_next_with_scientific_notation_disabled() {
+        let config = Config { disable_scientific_notation: true, ..Config::default() };
+        let mut ts = TokenStream::with_config("2e3", &config);
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(2.0)));
+        assert_eq!(ts.next().unwrap(), Some(Token::Name("e3".to_string())));
+        assert_eq!(ts.next().unwrap(), None);
+    }
+
+    #[test]
+    fn test_next_with_grouped_number_input() {
+        let config = Config { grouped_number_input: true, ..Config::default() };
+        let mut ts = TokenStream::with_config("1,234.56 + 1,000,000", &config);
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(1234.56)));
+        assert_eq!(ts.next().unwrap(), Some(Token::Symbol('+')));
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(1000000.0)));
+    }
+
+    #[test]
+    fn test_next_with_comma_after_number_in_grouped_input() {
+        let config = Config { grouped_number_input: true, ..Config::default() };
+        let mut ts = TokenStream::with_config("1, 2", &config);
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(1.0)));
+        assert_eq!(ts.next().unwrap(), Some(Token::Symbol(',')));
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(2.0)));
+    }
+
+    #[test]
+    fn test_next_with_comma_between_numbers() {
+        TestCase::input("1,234").expect(vec![
+            Token::Number(1.0),
+            Token::Symbol(','),
+            Token::Number(234.0),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_number_with_scientific_notation() {
+        assert_eq!(parse_number("1.23e-4").unwrap(), 1.23e-4);
+    }
+
+    #[test]
+    fn test_parse_number_with_surrounding_whitespace() {
+        assert_eq!(parse_number("  42 ").unwrap(), 42.0);
+    }
+
+    #[test]
+    fn test_parse_number_with_invalid_number() {
+        let err = parse_number("1.2.3").unwrap_err().downcast::<TokenizationError>().unwrap();
+        assert_eq!(err, TokenizationError::InvalidNumber("1.2.3".to_string()));
+    }
+
+    #[test]
+    fn test_parse_number_with_trailing_input() {
+        assert!(parse_number("12abc").is_err());
+        assert!(parse_number("").is_err());
+        assert!(parse_number("-1").is_err());
+    }
+
+    #[test]
+    fn test_next_with_underscore_in_name() {
+        TestCase::input("wrap_signed _x1").expect(vec![
+            Token::Name("wrap_signed".to_string()),
+            Token::Name("_x1".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_next_with_logical_xor() {
+        TestCase::input("1 ^^ 0").expect(vec![
+            Token::Number(1.0),
+            Token::LogicalXor,
+            Token::Number(0.0),
+        ]);
+    }
+
+    #[test]
+    fn test_next_with_pipe() {
+        TestCase::input("16|>sqrt").expect(vec![
+            Token::Number(16.0),
+            Token::Pipe,
+            Token::Name("sqrt".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_next_with_fallback() {
+        TestCase::input("x??5").expect(vec![
+            Token::Name("x".to_string()),
+            Token::Fallback,
+            Token::Number(5.0),
+        ]);
+    }
+
+    #[test]
+    fn test_nesting_follows_consumed_parentheses() {
+        let mut ts = TokenStream::new("((1) 2".as_bytes());
+        ts.next().unwrap();
+        ts.next().unwrap();
+        assert_eq!(ts.nesting(), 2);
+        ts.peek().unwrap();
+        ts.next().unwrap();
+        ts.next().unwrap();
+        assert_eq!(ts.nesting(), 1);
+        ts.put_back(Token::Symbol(')'));
+        assert_eq!(ts.nesting(), 2);
+    }
+
+    #[test]
+    fn test_reset_to_mark_rereads_tokens() {
+        let mut ts = TokenStream::new("1 + (2 * x)".as_bytes());
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(1.0)));
+        ts.peek().unwrap();
+
+        let mark = ts.mark();
+        let mut first_read = vec![];
+        while let Some(token) = ts.next().unwrap() {
+            first_read.push(token);
+        }
+        assert_eq!(ts.nesting(), 0);
+
+        ts.reset_to(mark);
+        let mut second_read = vec![];
+        while let Some(token) = ts.next().unwrap() {
+            second_read.push(token);
+        }
+
+        assert_eq!(first_read, second_read);
+        assert_eq!(second_read[0], Token::Symbol('+'), "The peeked token should be restored too");
+    }
+
+    #[test]
+    fn test_reset_to_mark_restores_nesting() {
+        let mut ts = TokenStream::new("((1".as_bytes());
+        ts.next().unwrap();
+        let mark = ts.mark();
+        ts.next().unwrap();
+        assert_eq!(ts.nesting(), 2);
+        ts.reset_to(mark);
+        assert_eq!(ts.nesting(), 1);
+        assert_eq!(ts.next().unwrap(), Some(Token::Symbol('(')));
+    }
+
+    #[test]
+    fn test_next_with_unicode_math() {
+        TestCase::input("√16 * π").expect(vec![
+            Token::Symbol('√'),
+            Token::Number(16.0),
+            Token::Symbol('*'),
+            Token::Name("π".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_next_with_unsupported_unicode_symbol() {
+        TestCase::input("∑").expect_err(TokenizationError::InvalidSymbol('∑'));
+    }
+
+    #[test]
+    fn test_next_with_power() {
+        TestCase::input("2 ^ 3 ** 4 * 5").expect(vec![
+            Token::Number(2.0),
+            Token::Symbol('^'),
+            Token::Number(3.0),
+            Token::Symbol('^'),
+            Token::Number(4.0),
+            Token::Symbol('*'),
+            Token::Number(5.0),
+        ]);
+    }
+
     #[test]
     fn test_next_with_unexpected_symbol() {
         TestCase::input("123 + * 456").expect(vec![
@@ -390,4 +842,148 @@ mod tests {
             Token::Number(456.0),
         ]);
     }
+
+    #[test]
+    fn test_from_chars_matches_byte_input() {
+        let input = "let π2 = √(1,5) ^^ x |> sqrt; q";
+        let mut from_bytes = TokenStream::new(input.as_bytes());
+        let mut from_chars = TokenStream::from_chars(input);
+        loop {
+            let token = from_chars.next().unwrap();
+            assert_eq!(token, from_bytes.next().unwrap());
+            if token.is_none() { break; }
+        }
+    }
+
+    #[test]
+    fn test_new_replaces_invalid_utf8() {
+        let mut ts = TokenStream::new(b"1 \xff");
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(1.0)));
+        assert!(ts.next().is_err());
+    }
+
+    #[test]
+    fn test_next_with_brackets() {
+        TestCase::input("[1]{2}")
+            .expect(vec![
+                Token::Symbol('['),
+                Token::Number(1.0),
+                Token::Symbol(']'),
+                Token::Symbol('{'),
+                Token::Number(2.0),
+                Token::Symbol('}'),
+            ]);
+    }
+
+    #[test]
+    fn test_nesting_counts_all_bracket_types() {
+        let mut ts = TokenStream::from_chars("([{1");
+        for _ in 0..3 { ts.next().unwrap(); }
+        assert_eq!(ts.nesting(), 3);
+        ts.put_back(Token::Symbol('{'));
+        assert_eq!(ts.nesting(), 2);
+    }
+
+    #[test]
+    fn test_next_with_label_and_reference() {
+        TestCase::input("total: 1; @total")
+            .expect(vec![
+                Token::Name("total".to_string()),
+                Token::Symbol(':'),
+                Token::Number(1.0),
+                Token::EndStatement,
+                Token::Reference("total".to_string()),
+            ]);
+    }
+
+    #[test]
+    fn test_next_with_quoted_text() {
+        TestCase::input("from_roman(\"XIV\")")
+            .expect(vec![
+                Token::Name("from_roman".to_string()),
+                Token::Symbol('('),
+                Token::Text("XIV".to_string()),
+                Token::Symbol(')'),
+            ]);
+    }
+
+    #[test]
+    fn test_next_with_unclosed_text() {
+        TestCase::input("\"XIV")
+            .expect_err(TokenizationError::UnclosedText("XIV".to_string()));
+    }
+
+    #[test]
+    fn test_next_with_reference_without_name() {
+        TestCase::input("@ 1")
+            .expect_err(TokenizationError::InvalidSymbol('@'));
+    }
+
+    #[test]
+    fn test_next_with_single_char_after_end_statement() {
+        TestCase::input("1;2")
+            .expect(vec![Token::Number(1.0), Token::EndStatement, Token::Number(2.0)]);
+        TestCase::input("1;")
+            .expect(vec![Token::Number(1.0)]);
+    }
+
+    #[test]
+    fn test_reparse_edit_near_end_reuses_prefix() {
+        let mut state = ReparseState::new("1 + 2 * 3");
+        state.edit(8, 1, "45");
+        assert_eq!(state.reused(), 4);
+        assert!(state.tokens().eq(ReparseState::new("1 + 2 * 45").tokens()));
+    }
+
+    #[test]
+    fn test_reparse_edit_extending_a_token() {
+        let mut state = ReparseState::new("12 + 3");
+        state.edit(2, 0, "5");
+        assert_eq!(state.reused(), 0);
+        assert!(state.tokens().eq(ReparseState::new("125 + 3").tokens()));
+
+        let mut state = ReparseState::new("2 *");
+        state.edit(3, 0, "* 3");
+        assert!(state.tokens().eq([Token::Number(2.0), Token::Symbol('^'), Token::Number(3.0)].iter()));
+    }
+
+    #[test]
+    fn test_reparse_edit_after_end_statement() {
+        let mut state = ReparseState::new("1;");
+        state.edit(2, 0, "2");
+        assert!(state.tokens().eq([Token::Number(1.0), Token::EndStatement, Token::Number(2.0)].iter()));
+    }
+
+    #[test]
+    fn test_reparse_deletion_in_the_middle() {
+        let mut state = ReparseState::new("let x = 10 + 20");
+        state.edit(8, 5, "");
+        assert_eq!(state.reused(), 3);
+        assert!(state.tokens().eq(ReparseState::new("let x = 20").tokens()));
+    }
+
+    #[test]
+    fn test_next_with_root_prefixes() {
+        TestCase::input("√∛∜8")
+            .expect(vec![Token::Symbol('√'), Token::Symbol('∛'), Token::Symbol('∜'), Token::Number(8.0)]);
+    }
+
+    #[test]
+    fn test_next_with_german_locale() {
+        let config = Config { locale: Locale::German, grouped_number_input: true, ..Config::default() };
+        let mut ts = TokenStream::with_config("1.234,56 + 2,5", &config);
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(1234.56)));
+        assert_eq!(ts.next().unwrap(), Some(Token::Symbol('+')));
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(2.5)));
+    }
+
+    #[test]
+    fn test_next_with_decimal_comma_before_argument_separator() {
+        let config = Config { locale: Locale::German, ..Config::default() };
+        let mut ts = TokenStream::with_config("1, 2,5", &config);
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(1.0)));
+        assert_eq!(ts.next().unwrap(), Some(Token::Symbol(',')));
+        assert_eq!(ts.next().unwrap(), Some(Token::Number(2.5)));
+        assert!(TokenStream::with_config("1.5", &config).nth(1).unwrap().is_err(), "A point is not a decimal separator in German");
+    }
 }